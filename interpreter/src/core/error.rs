@@ -12,6 +12,7 @@ const STYLE_BOLD: &str = "\x1b[1m";
 pub enum ErrorKind {
     Runtime,
     Syntax,
+    Dimension, // found by the dimension check, see `evaluator::dimensions`
 }
 
 #[derive(Debug, Clone)]
@@ -173,6 +174,7 @@ impl fmt::Display for Error {
         let kind = match self.kind {
             ErrorKind::Runtime => "Runtime Error",
             ErrorKind::Syntax => "Syntax Error",
+            ErrorKind::Dimension => "Dimension Error",
         };
 
         write!(
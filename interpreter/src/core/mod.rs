@@ -1,4 +1,5 @@
 pub mod value;
 pub mod logic;
 pub mod error;
-pub mod units;
\ No newline at end of file
+pub mod units;
+pub mod rounding;
\ No newline at end of file
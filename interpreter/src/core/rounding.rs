@@ -0,0 +1,166 @@
+use std::cell::Cell;
+
+// Directed rounding for interval bounds.
+// In rigorous mode every interval bound is rounded outward (lower bound down, upper bound up),
+// so the result always encloses the true real result. The error of each operation is recovered
+// exactly (TwoSum / FMA), so bounds are only widened when the operation was actually inexact.
+
+thread_local! {
+    static RIGOROUS: Cell<bool> = const { Cell::new(false) };
+}
+
+pub fn is_rigorous() -> bool {
+    RIGOROUS.with(|r| r.get())
+}
+
+/// Sets the rounding mode for the current thread, returns the previous one
+pub fn set_rigorous(rigorous: bool) -> bool {
+    RIGOROUS.with(|r| r.replace(rigorous))
+}
+
+fn down_if(value: f64, inexact_above: bool) -> f64 {
+    if inexact_above { value.next_down() } else { value }
+}
+
+fn up_if(value: f64, inexact_below: bool) -> f64 {
+    if inexact_below { value.next_up() } else { value }
+}
+
+// Error of a + b, exact as long as nothing overflows (Knuth's TwoSum)
+fn add_error(a: f64, b: f64, s: f64) -> f64 {
+    let bb = s - a;
+    (a - (s - bb)) + (b - bb)
+}
+
+pub fn add_down(a: f64, b: f64) -> f64 {
+    let s = a + b;
+    if !is_rigorous() || !s.is_finite() { return s; }
+    down_if(s, add_error(a, b, s) < 0.0)
+}
+
+pub fn add_up(a: f64, b: f64) -> f64 {
+    let s = a + b;
+    if !is_rigorous() || !s.is_finite() { return s; }
+    up_if(s, add_error(a, b, s) > 0.0)
+}
+
+pub fn sub_down(a: f64, b: f64) -> f64 {
+    add_down(a, -b)
+}
+
+pub fn sub_up(a: f64, b: f64) -> f64 {
+    add_up(a, -b)
+}
+
+pub fn mul_down(a: f64, b: f64) -> f64 {
+    let p = a * b;
+    if !is_rigorous() || !p.is_finite() { return p; }
+    down_if(p, a.mul_add(b, -p) < 0.0)
+}
+
+pub fn mul_up(a: f64, b: f64) -> f64 {
+    let p = a * b;
+    if !is_rigorous() || !p.is_finite() { return p; }
+    up_if(p, a.mul_add(b, -p) > 0.0)
+}
+
+// The remainder a - q*b has the sign of the error times the sign of b
+fn div_error_sign(a: f64, b: f64, q: f64) -> f64 {
+    (-q).mul_add(b, a) * b.signum()
+}
+
+pub fn div_down(a: f64, b: f64) -> f64 {
+    let q = a / b;
+    if !is_rigorous() || !q.is_finite() { return q; }
+    down_if(q, div_error_sign(a, b, q) < 0.0)
+}
+
+pub fn div_up(a: f64, b: f64) -> f64 {
+    let q = a / b;
+    if !is_rigorous() || !q.is_finite() { return q; }
+    up_if(q, div_error_sign(a, b, q) > 0.0)
+}
+
+pub fn sqrt_down(x: f64) -> f64 {
+    let r = x.sqrt();
+    if !is_rigorous() || !r.is_finite() { return r; }
+    down_if(r, r.mul_add(r, -x) > 0.0)
+}
+
+pub fn sqrt_up(x: f64) -> f64 {
+    let r = x.sqrt();
+    if !is_rigorous() || !r.is_finite() { return r; }
+    up_if(r, r.mul_add(r, -x) < 0.0)
+}
+
+/// x^n with the result rounded down, exact integer powers use repeated directed multiplication
+pub fn pow_down(x: f64, n: f64) -> f64 {
+    if !is_rigorous() {
+        return x.powf(n);
+    }
+    match integer_pow(x, n) {
+        Some((magnitude_down, magnitude_up, negative)) => {
+            if negative { -magnitude_up } else { magnitude_down }
+        }
+        None => down(x.powf(n)),
+    }
+}
+
+pub fn pow_up(x: f64, n: f64) -> f64 {
+    if !is_rigorous() {
+        return x.powf(n);
+    }
+    match integer_pow(x, n) {
+        Some((magnitude_down, magnitude_up, negative)) => {
+            if negative { -magnitude_down } else { magnitude_up }
+        }
+        None => up(x.powf(n)),
+    }
+}
+
+fn integer_pow(x: f64, n: f64) -> Option<(f64, f64, bool)> {
+    if n.fract() != 0.0 || !(0.0..=64.0).contains(&n) {
+        return None;
+    }
+
+    let base = x.abs();
+    let mut low = 1.0;
+    let mut high = 1.0;
+    for _ in 0..(n as u32) {
+        low = mul_down(low, base);
+        high = mul_up(high, base);
+    }
+
+    let negative = x < 0.0 && (n as u32) % 2 == 1;
+    Some((low, high, negative))
+}
+
+/// Rounds down a result that isn't correctly rounded (libm functions), assumes less than 1 ulp of error
+pub fn down(x: f64) -> f64 {
+    if is_rigorous() && x.is_finite() { x.next_down() } else { x }
+}
+
+pub fn up(x: f64) -> f64 {
+    if is_rigorous() && x.is_finite() { x.next_up() } else { x }
+}
+
+/// Lower bound for a number written in decimal, widened when the f64 isn't exactly that decimal
+pub fn literal_down(x: f64) -> f64 {
+    if !is_rigorous() || is_exact_decimal(x) { return x; }
+    x.next_down()
+}
+
+pub fn literal_up(x: f64) -> f64 {
+    if !is_rigorous() || is_exact_decimal(x) { return x; }
+    x.next_up()
+}
+
+// The shortest round-trip representation of x is taken as the decimal the user meant
+fn is_exact_decimal(x: f64) -> bool {
+    !x.is_finite() || exact_decimal(x) == x.to_string()
+}
+
+fn exact_decimal(x: f64) -> String {
+    let digits = format!("{:.1100}", x);
+    digits.trim_end_matches('0').trim_end_matches('.').to_string()
+}
@@ -1,20 +1,71 @@
 use std::collections::BTreeMap;
 use std::fmt;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 pub struct Unit {
-    dims: BTreeMap<String, i32>,
+    dims: BTreeMap<String, Ratio>,
+    display: Option<(Box<str>, f64)>, // the unit a value was written in and its size, only for printing
+    offset: f64, // zero of the scale in base units, nonzero on absolute values like degC
+    difference: bool, // a difference between absolute values, like deltaC or 30 degC - 20 degC
 }
 
+// Units are the same when their dimensions are, however they are displayed
+impl PartialEq for Unit {
+    fn eq(&self, other: &Self) -> bool {
+        self.dims == other.dims
+    }
+}
+
+impl Eq for Unit {}
+
 impl Unit {
     pub fn dimensionless() -> Self {
-        Self { dims: BTreeMap::new() }
+        Self { dims: BTreeMap::new(), display: None, offset: 0.0, difference: false }
     }
 
     pub fn base(symbol: &str) -> Self {
         let mut dims = BTreeMap::new();
-        dims.insert(symbol.to_string(), 1);
-        Self { dims }
+        dims.insert(symbol.to_string(), Ratio::from(1));
+        Self { dims, display: None, offset: 0.0, difference: false }
+    }
+
+    /// The same unit, printed as `name` where one of it is `scale` base units
+    pub fn displayed_as(mut self, name: String, scale: f64) -> Self {
+        self.display = Some((name.into_boxed_str(), scale));
+        self
+    }
+
+    pub fn display(&self) -> Option<(&str, f64)> {
+        self.display.as_ref().map(|(name, scale)| (name.as_ref(), *scale))
+    }
+
+    /// The same unit on a scale whose zero is `offset` base units, values in it are absolute
+    pub fn with_offset(mut self, offset: f64) -> Self {
+        self.offset = offset;
+        self.difference = false;
+        self
+    }
+
+    /// The same unit for differences of absolute values, which can't be read on an offset scale
+    pub fn as_difference(mut self) -> Self {
+        self.difference = true;
+        self
+    }
+
+    pub fn is_difference(&self) -> bool {
+        self.difference
+    }
+
+    pub fn offset(&self) -> Option<f64> {
+        Some(self.offset).filter(|offset| *offset != 0.0)
+    }
+
+    /// The unit of a difference or a multiple of a value in this unit, which is no longer absolute
+    pub fn relative(&self) -> Unit {
+        match self.offset() {
+            Some(_) => Unit { dims: self.dims.clone(), display: None, offset: 0.0, difference: true },
+            None => self.clone(),
+        }
     }
 
     pub fn is_dimensionless(&self) -> bool {
@@ -22,43 +73,130 @@ impl Unit {
     }
 
     pub fn mul(&self, other: &Unit) -> Unit {
-        let mut dims = self.dims.clone();
-        for (name, power) in &other.dims {
-            let entry = dims.entry(name.clone()).or_insert(0);
-            *entry += *power;
-            if *entry == 0 {
-                dims.remove(name);
-            }
-        }
-        Unit { dims }
+        self.checked_mul(other).expect("unit exponent out of range")
     }
 
     pub fn div(&self, other: &Unit) -> Unit {
+        self.checked_div(other).expect("unit exponent out of range")
+    }
+
+    pub fn pow(&self, exp: impl Into<Ratio>) -> Unit {
+        self.checked_pow(exp).expect("unit exponent out of range")
+    }
+
+    /// The unit of the `n`th root of a value in this unit, `m^2` gives `m`
+    pub fn root(&self, n: i32) -> Unit {
+        self.checked_root(n).expect("unit exponent out of range")
+    }
+
+    // The checked versions are `None` when an exponent no longer fits, like `m^2147483647 * m`
+
+    pub fn checked_mul(&self, other: &Unit) -> Option<Unit> {
         let mut dims = self.dims.clone();
         for (name, power) in &other.dims {
-            let entry = dims.entry(name.clone()).or_insert(0);
-            *entry -= *power;
-            if *entry == 0 {
+            let entry = dims.entry(name.clone()).or_insert(Ratio::from(0));
+            *entry = entry.add(*power)?;
+            if entry.is_zero() {
                 dims.remove(name);
             }
         }
-        Unit { dims }
+        Some(Unit { dims, display: None, offset: 0.0, difference: false })
     }
 
-    pub fn pow(&self, exp: i32) -> Unit {
-        if exp == 0 {
-            return Unit::dimensionless();
-        }
+    pub fn checked_div(&self, other: &Unit) -> Option<Unit> {
+        self.checked_mul(&other.checked_pow(Ratio::from(-1))?)
+    }
 
+    pub fn checked_pow(&self, exp: impl Into<Ratio>) -> Option<Unit> {
+        let exp = exp.into();
         let mut dims = BTreeMap::new();
         for (name, power) in &self.dims {
-            let next = power * exp;
-            if next != 0 {
+            let next = power.mul(exp)?;
+            if !next.is_zero() {
                 dims.insert(name.clone(), next);
             }
         }
-        Unit { dims }
+        Some(Unit { dims, display: None, offset: 0.0, difference: false })
+    }
+
+    pub fn checked_root(&self, n: i32) -> Option<Unit> {
+        self.checked_pow(Ratio::new(1, n)?)
+    }
+}
+
+/// A unit exponent, `m^2`, `m^-1` or `m^(1/2)`, kept in lowest terms with a positive denominator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ratio {
+    num: i32,
+    den: i32,
+}
+
+impl Ratio {
+    const MAX_DENOMINATOR: i32 = 12;
+
+    pub fn new(num: i32, den: i32) -> Option<Ratio> {
+        Self::reduced(num as i64, den as i64)
+    }
+
+    // Products of two ratios are worked out in `i64` and only fail if the reduced ratio doesn't fit
+    fn reduced(num: i64, den: i64) -> Option<Ratio> {
+        if den == 0 {
+            return None;
+        }
+        let divisor = gcd(num, den) * den.signum();
+        Some(Ratio { num: i32::try_from(num / divisor).ok()?, den: i32::try_from(den / divisor).ok()? })
+    }
+
+    /// The ratio a number is, for small denominators only, `0.5` is 1/2 and `0.3333333` isn't one
+    pub fn from_f64(value: f64) -> Option<Ratio> {
+        if !value.is_finite() || value.abs() > i32::MAX as f64 {
+            return None;
+        }
+        (1..=Self::MAX_DENOMINATOR).find_map(|den| {
+            let num = value * den as f64;
+            if (num - num.round()).abs() < 1e-9 { Ratio::reduced(num.round() as i64, den as i64) } else { None }
+        })
+    }
+
+    pub fn as_f64(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.num == 0
+    }
+
+    fn add(self, other: Ratio) -> Option<Ratio> {
+        let (a, b, c, d) = (self.num as i64, self.den as i64, other.num as i64, other.den as i64);
+        Ratio::reduced(a * d + c * b, b * d)
+    }
+
+    fn mul(self, other: Ratio) -> Option<Ratio> {
+        Ratio::reduced(self.num as i64 * other.num as i64, self.den as i64 * other.den as i64)
+    }
+}
+
+impl From<i32> for Ratio {
+    fn from(num: i32) -> Self {
+        Ratio { num, den: 1 }
+    }
+}
+
+impl fmt::Display for Ratio {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.den {
+            1 => write!(f, "{}", self.num),
+            den => write!(f, "({}/{})", self.num, den),
+        }
+    }
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
     }
+    a.max(1)
 }
 
 impl fmt::Display for Unit {
@@ -71,15 +209,15 @@ impl fmt::Display for Unit {
         let mut denominators = Vec::new();
 
         for (name, power) in &self.dims {
-            if *power > 0 {
-                if *power == 1 {
+            if power.num > 0 {
+                if *power == Ratio::from(1) {
                     numerators.push(name.clone());
                 } else {
                     numerators.push(format!("{}^{}", name, power));
                 }
-            } else if *power < 0 {
-                let abs_power = power.abs();
-                if abs_power == 1 {
+            } else if power.num < 0 {
+                let abs_power = Ratio { num: -power.num, den: power.den };
+                if abs_power == Ratio::from(1) {
                     denominators.push(name.clone());
                 } else {
                     denominators.push(format!("{}^{}", name, abs_power));
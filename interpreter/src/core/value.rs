@@ -2,10 +2,12 @@ use core::fmt;
 use crate::parser::ast::{Expr, Parameter, Stmt, UnitExpr};
 use crate::parser::lexer::{Token, TokenSpan};
 use crate::core::logic;
-use crate::core::units::Unit;
+use crate::core::rounding;
+use crate::core::units::{Ratio, Unit};
 use crate::core::error::Error;
 
 use crate::evaluator::env::Environment;
+use crate::evaluator::symbolic;
 use std::rc::Rc;
 use std::cell::RefCell;
 
@@ -21,6 +23,7 @@ pub type NativeFn = fn(Vec<Value>, TokenSpan, &mut crate::evaluator::eval::Evalu
 #[derive(Debug, Clone)]
 pub struct Function {
     pub params: Vec<Parameter>,
+    pub returns: Option<UnitExpr>, // the unit of the result, checked on every call
     pub body: Vec<Stmt>,
     pub closure: Rc<RefCell<Environment>>, 
     pub is_public: bool,
@@ -39,8 +42,12 @@ pub enum Value {
         expression: Box<Expr>,
         is_quiet: bool,
     },
+    SymbolicFn {
+        params: Vec<String>,
+        expression: Box<Expr>, // the body, with the parameters as free variables
+    },
     NativeFn(NativeFn),
-    Function(Function),
+    Function(Box<Function>),
     Module(Rc<RefCell<Environment>>),
     None,
 }
@@ -104,29 +111,74 @@ impl Value {
         }
     }
 
+    /// Splits an interval (or an interval quantity) at its midpoint
+    pub fn bisect(&self) -> Option<(Value, Value)> {
+        match self {
+            Value::Interval(min, max) => {
+                let mid = min + (max - min) / 2.0;
+                Some((Value::Interval(*min, mid), Value::Interval(mid, *max)))
+            }
+            Value::Quantity { value, unit } => {
+                let (low, high) = value.bisect()?;
+                Some((
+                    Value::Quantity { value: Box::new(low), unit: unit.clone() },
+                    Value::Quantity { value: Box::new(high), unit: unit.clone() },
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Smallest interval containing both values
+    #[allow(clippy::result_large_err)]
+    pub fn union_with(&self, other: &Value) -> Result<Value, Error> {
+        match (self, other) {
+            (Value::Quantity { value: a, unit: u1 }, Value::Quantity { value: b, unit: u2 }) => {
+                if u1 != u2 {
+                    return Err(Self::err("Unit mismatch in union".to_string()));
+                }
+                let inner = a.union_with(b)?;
+                Ok(Value::Quantity { value: Box::new(inner), unit: u1.clone() })
+            }
+            (Value::Number(a), Value::Number(b)) => {
+                if a == b { Ok(Value::Number(*a)) } else { Ok(Value::Interval(a.min(*b), a.max(*b))) }
+            }
+            (Value::Interval(min, max), Value::Number(n)) | (Value::Number(n), Value::Interval(min, max)) => {
+                Ok(Value::Interval(min.min(*n), max.max(*n)))
+            }
+            (Value::Interval(min1, max1), Value::Interval(min2, max2)) => {
+                Ok(Value::Interval(min1.min(*min2), max1.max(*max2)))
+            }
+            _ => Err(Self::err("Union expects numbers or intervals".to_string())),
+        }
+    }
+
+    pub fn operator_symbol(token: &Token) -> &'static str {
+        match token {
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Star => "*",
+            Token::Slash => "/",
+            Token::Caret => "^",
+            Token::Modulo => "%",
+            Token::EqualEqual => "==",
+            Token::BangEqual => "!=",
+            Token::Greater => ">",
+            Token::GreaterEqual => ">=",
+            Token::Less => "<",
+            Token::LessEqual => "<=",
+            Token::And => "&&",
+            Token::Or => "||",
+            _ => "?",
+        }
+    }
+
     fn format_expr(expr: &Expr) -> String {
         match expr {
             Expr::Binary { left, operator, right } => {
                 let l = Self::format_expr(left);
                 let r = Self::format_expr(right);
-                let op = match operator.token {
-                    Token::Plus => "+",
-                    Token::Minus => "-",
-                    Token::Star => "*",
-                    Token::Slash => "/",
-                    Token::Caret => "^",
-                    Token::Modulo => "%",
-                    Token::EqualEqual => "==",
-                    Token::BangEqual => "!=",
-                    Token::Greater => ">",
-                    Token::GreaterEqual => ">=",
-                    Token::Less => "<",
-                    Token::LessEqual => "<=",
-                    Token::And => "&&",
-                    Token::Or => "||",
-                    _ => "?",
-                };
-                format!("({} {} {})", l, op, r)
+                format!("({} {} {})", l, Self::operator_symbol(&operator.token), r)
             }
             Expr::Literal { value } => match &value.token {
                 Token::Number(n) => n.to_string(),
@@ -137,9 +189,19 @@ impl Value {
                 Token::Unknown => "unknown".to_string(),
                 _ => format!("{:?}", value.token),
             },
+            Expr::Constant { value } => value.to_string(),
             Expr::Variable { name } => {
                 if let Token::Identifier(n) = &name.token { n.clone() } else { format!("{:?}", name.token) }
             }
+            Expr::Unary { operator, right } => {
+                let op = if operator.token == Token::Bang { "!" } else { "-" };
+                format!("{}{}", op, Self::format_expr(right))
+            }
+            Expr::Call { callee, arguments, .. } => {
+                let arguments: Vec<String> = arguments.iter().map(Self::format_expr).collect();
+                format!("{}({})", Self::format_expr(callee), arguments.join(", "))
+            }
+            Expr::Get { object, name } => format!("{}.{}", Self::format_expr(object), name.token_to_string()),
             Expr::Grouping { expression } => format!("({})", Self::format_expr(expression)),
             Expr::Postfix { name, operator } => {
                 let n = match &name.token {
@@ -162,7 +224,7 @@ impl Value {
         }
     }
 
-    fn format_unit_expr(unit: &UnitExpr) -> String {
+    pub fn format_unit_expr(unit: &UnitExpr) -> String {
         match unit {
             UnitExpr::Name(name) => name.token_to_string(),
             UnitExpr::Mul(left, right) => format!("{}*{}", Self::format_unit_expr(left), Self::format_unit_expr(right)),
@@ -171,6 +233,29 @@ impl Value {
         }
     }
 
+    // Smallest interval containing op(a, b) for every pair, lower bounds rounded down and upper bounds up
+    fn rounded_hull<const N: usize>(
+        pairs: [(f64, f64); N],
+        op_down: fn(f64, f64) -> f64,
+        op_up: fn(f64, f64) -> f64,
+    ) -> Value {
+        let low = pairs.iter().map(|(a, b)| op_down(*a, *b)).fold(f64::INFINITY, f64::min);
+        let high = pairs.iter().map(|(a, b)| op_up(*a, *b)).fold(f64::NEG_INFINITY, f64::max);
+        Value::Interval(low, high)
+    }
+
+    // In rigorous mode a number next to an interval stands for the decimal it was written as, so it
+    // is widened like a literal bound. Operations between numbers alone are left as they are
+    fn rigorous(left: &Value, right: &Value, op: fn(&Value, &Value) -> Result<Value, Error>) -> Option<Result<Value, Error>> {
+        let widened = match (left, right) {
+            _ if !rounding::is_rigorous() => return None,
+            (Value::Number(n), Value::Interval(..)) => (Value::Interval(rounding::literal_down(*n), rounding::literal_up(*n)), right.clone()),
+            (Value::Interval(..), Value::Number(n)) => (left.clone(), Value::Interval(rounding::literal_down(*n), rounding::literal_up(*n))),
+            _ => return None,
+        };
+        Some(op(&widened.0, &widened.1))
+    }
+
     fn err(msg: String) -> Error {
         Error::new(
             TokenSpan {
@@ -182,14 +267,38 @@ impl Value {
         )
     }
 
+    // A length plus an unknown can only be a length, the unit outlives the unknown
+    fn unknown_like(left: &Value, right: &Value, unit: fn(&Unit) -> Unit) -> Value {
+        match (left, right) {
+            (Value::Quantity { unit: u, .. }, _) | (_, Value::Quantity { unit: u, .. }) => {
+                Value::Quantity { value: Box::new(Value::Unknown), unit: unit(u) }
+            }
+            _ => Value::Unknown,
+        }
+    }
+
     pub fn add(&self, other: &Value) -> Result<Value, Error> {
+        if let (Value::Unknown, _) | (_, Value::Unknown) = (self, other) {
+            return Ok(Self::unknown_like(self, other, Unit::clone));
+        }
+        if let Some(result) = Self::rigorous(self, other, Value::add) {
+            return result;
+        }
+
         match (self, other) {
             (Value::Quantity { value: a, unit: u1 }, Value::Quantity { value: b, unit: u2 }) => {
                 if u1 != u2 {
                     return Err(Self::err("Unit mismatch in addition".to_string()));
                 }
+                let unit = match (u1.offset(), u2.offset()) {
+                    (Some(_), Some(_)) => {
+                        return Err(Self::err("Cannot add two absolute temperatures, subtract them to get a difference".to_string()));
+                    }
+                    (None, Some(_)) => u2.clone(),
+                    _ => u1.clone(),
+                };
                 let inner = a.as_ref().add(b.as_ref())?;
-                Ok(Value::Quantity { value: Box::new(inner), unit: u1.clone() })
+                Ok(Value::Quantity { value: Box::new(inner), unit })
             }
             (Value::Quantity { .. }, _) | (_, Value::Quantity { .. }) => {
                 Err(Self::err("Cannot add a unit value to a non-unit value".to_string()))
@@ -198,10 +307,10 @@ impl Value {
             (Value::String(s1), Value::String(s2)) => Ok(Value::String(format!("{}{}", s1, s2))),
 
             (Value::Interval(min, max), Value::Number(n)) | (Value::Number(n), Value::Interval(min, max)) => {
-                Ok(Value::Interval(min + n, max + n))
+                Ok(Value::Interval(rounding::add_down(*min, *n), rounding::add_up(*max, *n)))
             }
             (Value::Interval(min1, max1), Value::Interval(min2, max2)) => {
-                Ok(Value::Interval(min1 + min2, max1 + max2))
+                Ok(Value::Interval(rounding::add_down(*min1, *min2), rounding::add_up(*max1, *max2)))
             },
 
             _ => Err(Self::err("Invalid types for addition".to_string())),
@@ -209,13 +318,27 @@ impl Value {
     }
 
     pub fn sub(&self, other: &Value) -> Result<Value, Error> {
+        if let (Value::Unknown, _) | (_, Value::Unknown) = (self, other) {
+            return Ok(Self::unknown_like(self, other, Unit::relative));
+        }
+        if let Some(result) = Self::rigorous(self, other, Value::sub) {
+            return result;
+        }
+
         match (self, other) {
             (Value::Quantity { value: a, unit: u1 }, Value::Quantity { value: b, unit: u2 }) => {
                 if u1 != u2 {
                     return Err(Self::err("Unit mismatch in subtraction".to_string()));
                 }
+                let unit = match (u1.offset(), u2.offset()) {
+                    (Some(_), Some(_)) => u1.relative(), // two temperatures give a difference
+                    (None, Some(_)) => {
+                        return Err(Self::err("Cannot subtract an absolute temperature from a difference".to_string()));
+                    }
+                    _ => u1.clone(),
+                };
                 let inner = a.as_ref().sub(b.as_ref())?;
-                Ok(Value::Quantity { value: Box::new(inner), unit: u1.clone() })
+                Ok(Value::Quantity { value: Box::new(inner), unit })
             }
             (Value::Quantity { .. }, _) | (_, Value::Quantity { .. }) => {
                 Err(Self::err("Cannot subtract a unit value and a non-unit value".to_string()))
@@ -223,11 +346,15 @@ impl Value {
             (l, r) if l == r && !l.is_symbolic_or_unknown() => Ok(Value::Number(0.0)),
             (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a - b)),
 
-            (Value::Interval(min, max), Value::Number(n)) => Ok(Value::Interval(min - n, max - n)),
-            (Value::Number(n), Value::Interval(min, max)) => Ok(Value::Interval(n - max, n - min)),
+            (Value::Interval(min, max), Value::Number(n)) => {
+                Ok(Value::Interval(rounding::sub_down(*min, *n), rounding::sub_up(*max, *n)))
+            }
+            (Value::Number(n), Value::Interval(min, max)) => {
+                Ok(Value::Interval(rounding::sub_down(*n, *max), rounding::sub_up(*n, *min)))
+            }
 
             (Value::Interval(min1, max1), Value::Interval(min2, max2)) => {
-                Ok(Value::Interval(min1 - max2, max1 - min2))
+                Ok(Value::Interval(rounding::sub_down(*min1, *max2), rounding::sub_up(*max1, *min2)))
             },
 
             _ => Err(Self::err("Invalid types for subtraction".to_string())),
@@ -235,33 +362,43 @@ impl Value {
     }
 
     pub fn mul(&self, other: &Value) -> Result<Value, Error> {
+        if let (Value::Unknown, _) | (_, Value::Unknown) = (self, other) {
+            return Ok(Value::Unknown);
+        }
+        let is_zero = |value: &Value| matches!(value, Value::Number(n) if *n == 0.0);
+        if !is_zero(self) && !is_zero(other) && let Some(result) = Self::rigorous(self, other, Value::mul) {
+            return result;
+        }
+
         match (self, other) {
             (Value::Quantity { value: a, unit: u1 }, Value::Quantity { value: b, unit: u2 }) => {
                 let inner = a.as_ref().mul(b.as_ref())?;
-                Ok(Value::Quantity { value: Box::new(inner), unit: u1.mul(u2) })
+                Ok(Value::Quantity { value: Box::new(inner), unit: Self::exponents(u1.checked_mul(u2))? })
             }
             (Value::Quantity { value, unit }, Value::Number(_)) => {
                 let inner = value.as_ref().mul(other)?;
-                Ok(Value::Quantity { value: Box::new(inner), unit: unit.clone() })
+                Ok(Value::Quantity { value: Box::new(inner), unit: unit.relative() })
             }
             (Value::Number(_), Value::Quantity { value, unit }) => {
                 let inner = self.mul(value.as_ref())?;
-                Ok(Value::Quantity { value: Box::new(inner), unit: unit.clone() })
+                Ok(Value::Quantity { value: Box::new(inner), unit: unit.relative() })
             }
             (Value::Number(n), _) | (_, Value::Number(n)) if *n == 0.0 => Ok(Value::Number(0.0)),
             (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a * b)),
 
             (Value::Interval(min, max), Value::Number(n)) | (Value::Number(n), Value::Interval(min, max)) => {
-                let a = min * n;
-                let b = max * n;
-                Ok(Value::Interval(a.min(b), a.max(b)))
+                Ok(Self::rounded_hull(
+                    [(*min, *n), (*max, *n)],
+                    rounding::mul_down,
+                    rounding::mul_up,
+                ))
             },
 
             (Value::Interval(min1, max1), Value::Interval(min2, max2)) => {
-                let p = [min1 * min2, min1 * max2, max1 * min2, max1 * max2];
-                Ok(Value::Interval(
-                    p.iter().copied().fold(f64::INFINITY, f64::min),
-                    p.iter().copied().fold(f64::NEG_INFINITY, f64::max)
+                Ok(Self::rounded_hull(
+                    [(*min1, *min2), (*min1, *max2), (*max1, *min2), (*max1, *max2)],
+                    rounding::mul_down,
+                    rounding::mul_up,
                 ))
             },
 
@@ -277,15 +414,15 @@ impl Value {
         match (self, other) {
             (Value::Quantity { value: a, unit: u1 }, Value::Quantity { value: b, unit: u2 }) => {
                 let inner = a.as_ref().div(b.as_ref())?;
-                return Ok(Value::Quantity { value: Box::new(inner), unit: u1.div(u2) });
+                return Ok(Value::Quantity { value: Box::new(inner), unit: Self::exponents(u1.checked_div(u2))? });
             }
             (Value::Quantity { value, unit }, Value::Number(_)) => {
                 let inner = value.as_ref().div(other)?;
-                return Ok(Value::Quantity { value: Box::new(inner), unit: unit.clone() });
+                return Ok(Value::Quantity { value: Box::new(inner), unit: unit.relative() });
             }
             (Value::Number(_), Value::Quantity { value, unit }) => {
                 let inner = self.div(value.as_ref())?;
-                return Ok(Value::Quantity { value: Box::new(inner), unit: Unit::dimensionless().div(unit) });
+                return Ok(Value::Quantity { value: Box::new(inner), unit: Self::exponents(Unit::dimensionless().checked_div(unit))? });
             }
             _ => {}
         }
@@ -298,6 +435,10 @@ impl Value {
             }
         }
 
+        if !matches!(other, Value::Number(b) if *b == 0.0) && let Some(result) = Self::rigorous(self, other, Value::div) {
+            return result;
+        }
+
         match (self, other) {
             (Value::Number(a), Value::Number(b)) => {
                 if *b == 0.0 { return Err(Self::err("Division by zero!".to_string())); }
@@ -305,40 +446,23 @@ impl Value {
             }
             (Value::Interval(a_min, a_max), Value::Number(b)) => {
                 if *b == 0.0 { return Err(Self::err("Division by zero!".to_string())); }
-                let vals = [a_min / b, a_max / b];
-                Ok(Value::Interval(
-                    vals.iter().copied().fold(f64::INFINITY, f64::min),
-                    vals.iter().copied().fold(f64::NEG_INFINITY, f64::max),
-                ))
+                Ok(Self::rounded_hull([(*a_min, *b), (*a_max, *b)], rounding::div_down, rounding::div_up))
             }
             (Value::Number(a), Value::Interval(b_min, b_max)) => {
                 if *b_min <= 0.0 && *b_max >= 0.0 {
                     return Err(Self::err("Division by interval containing zero".to_string()));
                 }
-                let vals = [a / b_min, a / b_max];
-                Ok(Value::Interval(
-                    vals.iter().copied().fold(f64::INFINITY, f64::min),
-                    vals.iter().copied().fold(f64::NEG_INFINITY, f64::max),
-                ))
+                Ok(Self::rounded_hull([(*a, *b_min), (*a, *b_max)], rounding::div_down, rounding::div_up))
             }
             (Value::Interval(a_min, a_max), Value::Interval(b_min, b_max)) => {
                 if *b_min <= 0.0 && *b_max >= 0.0 {
                     return Err(Self::err("Division by interval containing zero".to_string()));
                 }
 
-                let b_recip_min = 1.0 / b_max;
-                let b_recip_max = 1.0 / b_min;
-
-                let ips = [
-                    a_min * b_recip_min,
-                    a_min * b_recip_max,
-                    a_max * b_recip_min,
-                    a_max * b_recip_max,
-                ];
-
-                Ok(Value::Interval(
-                    ips.iter().copied().fold(f64::INFINITY, f64::min),
-                    ips.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                Ok(Self::rounded_hull(
+                    [(*a_min, *b_min), (*a_min, *b_max), (*a_max, *b_min), (*a_max, *b_max)],
+                    rounding::div_down,
+                    rounding::div_up,
                 ))
             }
             _ => Err(Self::err("Division not supported for these types".to_string())),
@@ -346,13 +470,17 @@ impl Value {
     }
 
     pub fn pow(&self, other: &Value) -> Result<Value, Error> {
+        if let (Value::Unknown, _) | (_, Value::Unknown) = (self, other) {
+            return Ok(Value::Unknown);
+        }
+
         match (self, other) {
             (Value::Quantity { value, unit }, Value::Number(n)) => {
-                if n.fract() != 0.0 {
-                    return Err(Self::err("Unit exponent must be an integer".to_string()));
-                }
+                let Some(exponent) = Ratio::from_f64(*n) else {
+                    return Err(Self::err("Unit exponent must be a whole number or a simple fraction".to_string()));
+                };
                 let inner = value.as_ref().pow(other)?;
-                Ok(Value::Quantity { value: Box::new(inner), unit: unit.pow(*n as i32) })
+                Ok(Value::Quantity { value: Box::new(inner), unit: Self::exponents(unit.checked_pow(exponent))? })
             }
             (Value::Quantity { .. }, _) => {
                 Err(Self::err("Exponent must be a number for unit values".to_string()))
@@ -363,10 +491,8 @@ impl Value {
             (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a.powf(*b))),
 
             (Value::Interval(min, max), Value::Number(n)) => {
-                let p1 = min.powf(*n);
-                let p2 = max.powf(*n);
-                let mut low = p1.min(p2);
-                let high = p1.max(p2);
+                let mut low = rounding::pow_down(*min, *n).min(rounding::pow_down(*max, *n));
+                let high = rounding::pow_up(*min, *n).max(rounding::pow_up(*max, *n));
                 if n % 2.0 == 0.0 && *min <= 0.0 && *max >= 0.0 { 
                     low = 0.0; 
                 }
@@ -377,6 +503,10 @@ impl Value {
     }
 
     pub fn modulo(&self, other: &Value) -> Result<Value, Error> {
+        if let (Value::Unknown, _) | (_, Value::Unknown) = (self, other) {
+            return Ok(Value::Unknown);
+        }
+
         match (self, other) {
             (Value::Quantity { value: a, unit: u1 }, Value::Quantity { value: b, unit: u2 }) => {
                 if u1 != u2 {
@@ -463,9 +593,10 @@ impl Value {
                 _ => Ok(Value::Bool(SKBool::Partial)),
             },
 
-            (Value::Symbolic { expression: e1, is_quiet: q1 }, Value::Symbolic { expression: e2, is_quiet: q2 }) => match op {
-                Token::EqualEqual => Ok(Value::Bool(if e1 == e2 && q1 == q2 { SKBool::True } else { SKBool::False })),
-                Token::BangEqual => Ok(Value::Bool(if e1 != e2 || q1 != q2 { SKBool::True } else { SKBool::False })),
+            // Different formulas can still be equal, the evaluator resolves them to find out
+            (Value::Symbolic { expression: e1, .. }, Value::Symbolic { expression: e2, .. }) => match op {
+                Token::EqualEqual if symbolic::same_formula(e1, e2) => Ok(Value::Bool(SKBool::True)),
+                Token::BangEqual if symbolic::same_formula(e1, e2) => Ok(Value::Bool(SKBool::False)),
                 _ => Ok(Value::Bool(SKBool::Partial)),
             },
 
@@ -473,6 +604,69 @@ impl Value {
         }
     }
 
+    /// The number or interval a quantity is in the unit `target`, given as one of it, like `1 km`
+    #[allow(clippy::result_large_err)]
+    pub fn in_unit(&self, target: &Value) -> Result<Value, Error> {
+        let (scale, target_unit) = match target {
+            Value::Quantity { value, unit } => match value.as_ref() {
+                Value::Number(n) if *n != unit.offset().unwrap_or(0.0) => (*n - unit.offset().unwrap_or(0.0), unit),
+                _ => return Err(Self::err("The unit to convert to must be a single number of it".to_string())),
+            },
+            _ => return Err(Self::err(format!("Expected a unit to convert to, got '{}'", target))),
+        };
+
+        match self {
+            // A difference has no zero to count from, `(30 degC - 20 degC).in(degC)` would read -263.15
+            Value::Quantity { unit, .. } if unit == target_unit && unit.is_difference() && target_unit.offset().is_some() => {
+                Err(Self::err(format!("Cannot convert a difference in {} to an absolute temperature, add it to one instead", unit)))
+            }
+            Value::Quantity { unit, .. } if unit == target_unit && unit.offset().is_some() && target_unit.is_difference() => {
+                Err(Self::err("Cannot convert an absolute temperature to a difference, subtract another temperature from it".to_string()))
+            }
+            Value::Quantity { value, unit } if unit == target_unit => {
+                Ok(Self::tidy(Self::scaled_from(value, target_unit.offset(), scale)?))
+            }
+            Value::Quantity { unit, .. } => Err(Self::err(format!("Cannot convert {} to {}", unit, target_unit))),
+            _ => Err(Self::err(format!("Only unit values can be converted, got '{}'", self))),
+        }
+    }
+
+    // The unit of a product or power, `m^2147483647 * m` has an exponent too large to keep
+    #[allow(clippy::result_large_err)]
+    fn exponents(unit: Option<Unit>) -> Result<Unit, Error> {
+        unit.ok_or_else(|| Self::err("Unit exponent is too large".to_string()))
+    }
+
+    // A value in base units counted in steps of `scale` from `offset`
+    #[allow(clippy::result_large_err)]
+    fn scaled_from(value: &Value, offset: Option<f64>, scale: f64) -> Result<Value, Error> {
+        let shifted = match offset {
+            Some(offset) => value.sub(&Value::Number(offset))?,
+            None => value.clone(),
+        };
+        shifted.div(&Value::Number(scale))
+    }
+
+    // Converted values are rounded to 12 significant digits, so 20 degC reads 68 degF and not
+    // 67.99999999999994. Rigorous mode keeps the bounds as they are
+    fn tidy(value: Value) -> Value {
+        fn round(x: f64) -> f64 {
+            if x == 0.0 || !x.is_finite() {
+                return x;
+            }
+            let factor = 10f64.powi(11 - x.abs().log10().floor() as i32);
+            let rounded = (x * factor).round() / factor;
+            if rounded.is_finite() { rounded } else { x }
+        }
+
+        match value {
+            _ if rounding::is_rigorous() => value,
+            Value::Number(n) => Value::Number(round(n)),
+            Value::Interval(min, max) => Value::Interval(round(min), round(max)),
+            other => other,
+        }
+    }
+
     pub fn logic(&self, other: &Value, op: &Token) -> Result<Value, Error> {
         match (self, other) {
             (Value::Bool(a), Value::Bool(b)) => match op {
@@ -490,6 +684,9 @@ impl fmt::Display for Value {
         match self {
             Value::Number(n) => write!(f, "{}", n),
             Value::Quantity { value, unit } => {
+                if let Some((name, scale)) = unit.display() && let Ok(shown) = Self::scaled_from(value, unit.offset(), scale) {
+                    return write!(f, "{} {}", Self::tidy(shown), name);
+                }
                 if unit.is_dimensionless() {
                     write!(f, "{}", value)
                 } else {
@@ -502,6 +699,9 @@ impl fmt::Display for Value {
             Value::Bool(SKBool::Partial) => write!(f, "partial"),
             Value::Interval(min, max) => write!(f, "[{}..{}]", min, max),
             Value::Symbolic { expression, .. } => write!(f, "{}", Self::format_expr(expression)),
+            Value::SymbolicFn { params, expression } => {
+                write!(f, "({}) => {}", params.join(", "), Self::format_expr(expression))
+            }
             Value::Unknown => write!(f, "unknown"),
             Value::NativeFn(_) => write!(f, "<native fn>"),
             Value::Function(_) => write!(f, "<function>"),
@@ -1,5 +1,8 @@
 use crate::core::value::{Value, SKBool};
+use crate::core::rounding;
 use crate::evaluator::eval::Evaluator;
+use crate::evaluator::symbolic;
+use crate::evaluator::provenance;
 use crate::core::error::Error;
 use crate::parser::lexer::TokenSpan;
 use std::io::{self, Write};
@@ -21,15 +24,357 @@ pub fn write(args: Vec<Value>, _span: TokenSpan, _: &mut Evaluator) -> Result<Va
 
 pub fn resolve(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
     match args.first() {
-        Some(Value::Symbolic { expression, .. }) => {
-            eval.evaluate_expression(*expression.clone())
-        }
+        Some(Value::Symbolic { expression, .. }) => eval.resolve_expression(*expression.clone()),
         Some(Value::Unknown) => Ok(Value::None),
         Some(val) => Ok(val.clone()),
         None => Err(eval.error(span, "resolve() expects 1 argument")),
     }
 }
 
+// Bisects the interval inputs of a symbolic value and unions the results of every sub-box,
+// which removes most of the overestimation caused by reusing the same interval
+#[allow(clippy::result_large_err)]
+pub fn refine(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+    let expression = match args.first() {
+        Some(Value::Symbolic { expression, .. }) => *expression.clone(),
+        Some(_) => return Err(eval.error(span, "refine() expects a symbolic value")),
+        None => return Err(eval.error(span, "refine() expects a symbolic value and a depth")),
+    };
+
+    let depth = match args.get(1) {
+        Some(Value::Number(n)) if n.fract() == 0.0 && (0.0..=16.0).contains(n) => *n as u32,
+        None => 4,
+        _ => return Err(eval.error(span, "refine() depth must be a whole number from 0 to 16")),
+    };
+
+    let inputs = symbolic::collect_inputs(&eval.env.borrow(), &expression);
+    if let Some(name) = inputs.unknowns.first() {
+        return Err(eval.error(span, format!("refine() can't bisect unknown variable '{}'", name)));
+    }
+
+    let mut boxes = vec![inputs.intervals.clone()];
+    if !inputs.intervals.is_empty() {
+        for level in 0..depth as usize {
+            let axis = level % inputs.intervals.len();
+            boxes = boxes
+                .into_iter()
+                .flat_map(|current| {
+                    let mut low = current.clone();
+                    let mut high = current;
+                    if let Some((l, h)) = low[axis].1.bisect() {
+                        low[axis].1 = l;
+                        high[axis].1 = h;
+                    }
+                    [low, high]
+                })
+                .collect();
+        }
+    }
+
+    let mut enclosure: Option<Value> = None;
+    for current in &boxes {
+        let mut bindings = current.clone();
+        bindings.extend(inputs.formula_bindings());
+
+        let result = eval.evaluate_with_bindings(expression.clone(), bindings)?;
+        if !matches!(result, Value::Number(_) | Value::Interval(..) | Value::Quantity { .. }) {
+            return Err(eval.error(span, format!("refine() needs a numeric result, got '{}'", result)));
+        }
+
+        enclosure = Some(match enclosure {
+            None => result,
+            Some(previous) => previous
+                .union_with(&result)
+                .map_err(|e| eval.error(span.clone(), e.message))?,
+        });
+    }
+
+    Ok(Value::Array(vec![
+        enclosure.unwrap_or(Value::None),
+        Value::Number(boxes.len() as f64),
+    ]))
+}
+
+// Width of a numeric result, quantities are measured in their own unit
+fn width_of(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(_) => Some(0.0),
+        Value::Interval(min, max) => Some(rounding::sub_up(*max, *min)),
+        Value::Quantity { value, .. } => width_of(value),
+        _ => None,
+    }
+}
+
+fn midpoint_of(value: &Value) -> Value {
+    match value {
+        Value::Interval(min, max) => Value::Number(min + (max - min) / 2.0),
+        Value::Quantity { value, unit } => Value::Quantity { value: Box::new(midpoint_of(value)), unit: unit.clone() },
+        other => other.clone(),
+    }
+}
+
+// Fixes each input of a symbolic value at its midpoint, one at a time, and measures how much
+// narrower the result gets. Rows are [name, reduction], largest reduction first
+#[allow(clippy::result_large_err)]
+pub fn sensitivity(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+    let expression = match args.first() {
+        Some(Value::Symbolic { expression, .. }) => *expression.clone(),
+        _ => return Err(eval.error(span, "sensitivity() expects a symbolic value")),
+    };
+
+    let inputs = symbolic::collect_inputs(&eval.env.borrow(), &expression);
+    let evaluate = |bindings: Vec<(String, Value)>, eval: &mut Evaluator| -> Result<(Value, f64), Error> {
+        let mut bindings = bindings;
+        bindings.extend(inputs.formula_bindings());
+        let result = eval.evaluate_with_bindings(expression.clone(), bindings)?;
+        match width_of(&result) {
+            Some(width) => Ok((result, width)),
+            None => Err(eval.error(span.clone(), format!("sensitivity() needs a numeric result, got '{}'", result))),
+        }
+    };
+
+    // unknown inputs come first, nothing can be said about the result until they are known
+    let mut rows: Vec<Value> = inputs
+        .unknowns
+        .iter()
+        .map(|name| Value::Array(vec![Value::String(name.clone()), Value::Unknown]))
+        .collect();
+    if !rows.is_empty() {
+        return Ok(Value::Array(rows));
+    }
+
+    let (_, total) = evaluate(inputs.intervals.clone(), eval)?;
+    let mut reductions = Vec::new();
+    for (index, (name, value)) in inputs.intervals.iter().enumerate() {
+        let mut bindings = inputs.intervals.clone();
+        bindings[index].1 = midpoint_of(value);
+
+        let (result, width) = evaluate(bindings, eval)?;
+        let reduction = (total - width).max(0.0);
+        let row = match result {
+            Value::Quantity { unit, .. } => Value::Quantity { value: Box::new(Value::Number(reduction)), unit },
+            _ => Value::Number(reduction),
+        };
+        reductions.push((name.clone(), reduction, row));
+    }
+
+    reductions.sort_by(|a, b| b.1.total_cmp(&a.1));
+    rows.extend(reductions.into_iter().map(|(name, _, row)| Value::Array(vec![Value::String(name), row])));
+    Ok(Value::Array(rows))
+}
+
+#[allow(clippy::result_large_err)]
+pub fn to(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+    match (args.first(), args.get(1)) {
+        (Some(value), Some(unit)) => value.in_unit(unit).map_err(|e| eval.error(span, e.message)),
+        _ => Err(eval.error(span, "to() expects a unit value and a unit, like to(d, km)")),
+    }
+}
+
+// Derivative of a symbolic function, or of a symbolic value with respect to one of its variables
+#[allow(clippy::result_large_err)]
+pub fn derivative(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+    let variable = match args.get(1) {
+        Some(Value::String(name)) => Some(name.clone()),
+        None => None,
+        Some(_) => return Err(eval.error(span, "derivative() expects the variable name as a string")),
+    };
+
+    let (expression, variable, params) = match args.first() {
+        Some(Value::SymbolicFn { params, expression }) => {
+            let Some(variable) = variable.or_else(|| params.first().cloned()) else {
+                return Err(eval.error(span, "derivative() of a function without parameters is always 0"));
+            };
+            if !params.contains(&variable) {
+                return Err(eval.error(span, format!("'{}' is not a parameter of the function", variable)));
+            }
+            (symbolic::inline_body(&eval.env.borrow(), expression, params), variable, Some(params.clone()))
+        }
+        Some(Value::Symbolic { expression, .. }) => {
+            let Some(variable) = variable else {
+                return Err(eval.error(span, "derivative() of a symbolic value needs the variable name, like derivative(A, \"x\")"));
+            };
+            let inputs = symbolic::collect_inputs(&eval.env.borrow(), expression);
+            (symbolic::inline_formulas(expression, &inputs.formulas), variable, None)
+        }
+        _ => return Err(eval.error(span, "derivative() expects a symbolic function or a symbolic value")),
+    };
+
+    let Some(derived) = symbolic::derivative(&expression, &variable, &eval.env.borrow()) else {
+        return Err(eval.error(span, "derivative() can't differentiate this expression"));
+    };
+
+    Ok(match params {
+        Some(params) => Value::SymbolicFn { params, expression: Box::new(derived) },
+        None => Value::Symbolic { expression: Box::new(derived), is_quiet: false },
+    })
+}
+
+// Dependency graph of a symbolic value as Graphviz DOT text, every node shows its current enclosure
+#[allow(clippy::result_large_err)]
+pub fn graph(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+    let expression = match args.first() {
+        Some(Value::Symbolic { expression, .. }) => *expression.clone(),
+        _ => return Err(eval.error(span, "graph() expects a symbolic value")),
+    };
+
+    let inputs = symbolic::collect_inputs(&eval.env.borrow(), &expression);
+    let bindings = inputs.formula_bindings();
+    let dot = symbolic::dot_graph(&expression, &inputs.formulas, &mut |expr| {
+        eval.evaluate_with_bindings(expr.clone(), bindings.clone())
+    })?;
+    Ok(Value::String(dot))
+}
+
+// Evaluates a function or symbolic value for every combination of cases, given as [name, cases] pairs.
+// Returns rows of [case..., result], the first row holds the names
+#[allow(clippy::result_large_err)]
+pub fn scenarios(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+    let target = match args.first() {
+        Some(value @ (Value::Function(_) | Value::NativeFn(_) | Value::Symbolic { .. })) => value.clone(),
+        _ => return Err(eval.error(span, "scenarios() expects a function or a symbolic value")),
+    };
+
+    let pairs = match args.get(1) {
+        Some(Value::Array(pairs)) => pairs.clone(),
+        _ => return Err(eval.error(span, "scenarios() expects an array of [name, cases] pairs")),
+    };
+
+    let mut names = Vec::new();
+    let mut cases = Vec::new();
+    for pair in pairs {
+        if let Value::Array(items) = &pair && let [Value::String(name), Value::Array(values)] = items.as_slice() {
+            names.push(name.clone());
+            cases.push(values.clone());
+        } else {
+            return Err(eval.error(span, format!("scenarios() expects [name, cases] pairs, got '{}'", pair)));
+        }
+    }
+
+    let total: usize = cases.iter().map(|c| c.len()).product();
+    if total > 10_000 {
+        return Err(eval.error(span, format!("scenarios() would evaluate {} combinations, the limit is 10000", total)));
+    }
+
+    // functions take the cases as arguments, matched to their parameters by name
+    let order: Vec<usize> = match &target {
+        Value::Function(func) => {
+            let mut order = Vec::new();
+            for param in &func.params {
+                match names.iter().position(|n| *n == param.name.token_to_string()) {
+                    Some(index) => order.push(index),
+                    None if param.default.is_some() => break,
+                    None => {
+                        return Err(eval.error(span, format!("scenarios() needs cases for parameter '{}'", param.name.token_to_string())));
+                    }
+                }
+            }
+            if let Some(name) = names.iter().enumerate().find(|(i, _)| !order.contains(i)).map(|(_, n)| n) {
+                return Err(eval.error(span, format!("scenarios() got cases for '{}', which is not a parameter", name)));
+            }
+            order
+        }
+        Value::Symbolic { expression, .. } => {
+            // symbolic values take the cases as bindings, which must be variables they read
+            let env = eval.env.borrow();
+            let mut read = symbolic::free_variables(expression);
+            for (_, formula) in symbolic::collect_inputs(&env, expression).formulas {
+                read.extend(symbolic::free_variables(&formula));
+            }
+            if let Some(name) = names.iter().find(|n| !read.contains(n)) {
+                let message = format!("scenarios() got cases for '{}', which the symbolic value does not use", name);
+                drop(env);
+                return Err(eval.error(span, message));
+            }
+            (0..names.len()).collect()
+        }
+        _ => (0..names.len()).collect(),
+    };
+
+    let mut header: Vec<Value> = names.iter().cloned().map(Value::String).collect();
+    header.push(Value::String("result".into()));
+    let mut rows = vec![Value::Array(header)];
+
+    for index in 0..total {
+        // the first name changes slowest
+        let mut remaining = index;
+        let mut row = vec![Value::None; names.len()];
+        for (column, values) in cases.iter().enumerate().rev() {
+            row[column] = values[remaining % values.len()].clone();
+            remaining /= values.len();
+        }
+
+        let result = match &target {
+            Value::Symbolic { expression, .. } => {
+                let mut bindings = symbolic::collect_inputs(&eval.env.borrow(), expression).formula_bindings();
+                bindings.extend(names.iter().cloned().zip(row.iter().cloned()));
+                eval.evaluate_with_bindings(*expression.clone(), bindings)?
+            }
+            _ => {
+                let arguments = order.iter().map(|i| row[*i].clone()).collect();
+                eval.call_value(target.clone(), arguments, span.clone())?
+            }
+        };
+
+        row.push(result);
+        rows.push(Value::Array(row));
+    }
+
+    Ok(Value::Array(rows))
+}
+
+// Formats an array of rows as a text table with aligned columns, the first row is the header
+#[allow(clippy::result_large_err)]
+pub fn table(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+    let rows: Vec<Vec<String>> = match args.first() {
+        Some(Value::Array(rows)) => rows
+            .iter()
+            .map(|row| match row {
+                Value::Array(cells) => cells.iter().map(|c| c.to_string()).collect(),
+                other => vec![other.to_string()],
+            })
+            .collect(),
+        _ => return Err(eval.error(span, "table() expects an array of rows")),
+    };
+
+    let columns = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|c| rows.iter().filter_map(|r| r.get(c)).map(|cell| cell.chars().count()).max().unwrap_or(0))
+        .collect();
+
+    let format_row = |cells: &[String]| -> String {
+        let padded: Vec<String> = widths
+            .iter()
+            .enumerate()
+            .map(|(c, width)| format!("{:<width$}", cells.get(c).map(String::as_str).unwrap_or(""), width = width))
+            .collect();
+        padded.join(" | ").trim_end().to_string()
+    };
+
+    let mut lines = Vec::new();
+    for (index, row) in rows.iter().enumerate() {
+        lines.push(format_row(row));
+        if index == 0 {
+            lines.push(widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>().join("-+-"));
+        }
+    }
+    Ok(Value::String(lines.join("\n")))
+}
+
+// Prints where an uncertain value came from, following variables back to the inputs
+#[allow(clippy::result_large_err)]
+pub fn why(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+    let value = match args.first() {
+        Some(value) => value,
+        None => return Err(eval.error(span, "why() expects 1 argument")),
+    };
+
+    for line in provenance::explain(value, eval.argument_cause(0).as_deref()) {
+        println!("{}", line);
+    }
+    Ok(Value::None)
+}
+
 pub fn input(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
     if let Some(msg) = args.first() {
         print!("{}", msg);
@@ -89,6 +434,7 @@ pub fn possible(args: Vec<Value>, _span: TokenSpan, _: &mut Evaluator) -> Result
 pub fn known(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
     match args.first() {
         Some(Value::Unknown) | Some(Value::Symbolic { .. }) => Ok(Value::Bool(SKBool::False)),
+        Some(Value::Quantity { value, .. }) if **value == Value::Unknown => Ok(Value::Bool(SKBool::False)),
         Some(_) => Ok(Value::Bool(SKBool::True)),
         None => Err(eval.error(span, "known() expects 1 argument")),
     }
@@ -105,6 +451,7 @@ pub fn kind(args: Vec<Value>, _span: TokenSpan, _: &mut Evaluator) -> Result<Val
         Some(Value::Quantity { .. }) => "quantity",
         Some(Value::Symbolic { is_quiet: true, .. }) => "quiet",
         Some(Value::Symbolic { .. }) => "symbolic",
+        Some(Value::SymbolicFn { .. }) => "symbolic function",
         Some(Value::NativeFn(_)) => "native function",
         Some(Value::Function(_)) => "function",
         Some(Value::Module(_)) => "module",
@@ -152,7 +499,7 @@ pub fn mid(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Va
 
 pub fn width(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
     match args.first() {
-        Some(Value::Interval(min, max)) => Ok(Value::Number(max - min)),
+        Some(Value::Interval(min, max)) => Ok(Value::Number(rounding::sub_up(*max, *min))),
         _ => Err(eval.error(span, "width() expects an interval")),
     }
 }
\ No newline at end of file
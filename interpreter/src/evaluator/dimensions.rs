@@ -0,0 +1,513 @@
+use crate::core::error::{Error, ErrorKind, ErrorReporter};
+use crate::core::units::Unit;
+use crate::core::value::Value;
+use crate::evaluator::env::Environment;
+use crate::evaluator::symbolic;
+use crate::libs::units;
+use crate::parser::ast::{Expr, Parameter, Stmt, UnitExpr};
+use crate::parser::lexer::{Token, TokenSpan};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+// Dimension check: infers the unit of every variable and expression of a script without running
+// it, and reports `5 m + 3 s` or comparing a length to a time. Units come from the quantities
+// the script writes and from the unit libraries it imports. Anything that can't be told
+// statically, like user input, a call to a library or an unknown without a unit, is left out,
+// so the check only reports what is sure to fail
+
+const MAX_DEPTH: usize = 16; // nested calls of user functions followed, recursion stops earlier
+
+type Dimension = Option<Unit>;
+
+struct Function {
+    name: TokenSpan,
+    params: Vec<Parameter>,
+    returns: Option<UnitExpr>,
+    body: Body,
+}
+
+enum Body {
+    Statements(Vec<Stmt>),
+    Expression(Expr), // symbolic functions
+}
+
+pub struct DimensionChecker {
+    reporter: Rc<RefCell<ErrorReporter>>,
+    units: HashMap<String, Unit>, // unit names of the imported unit libraries
+    units_modules: HashSet<String>, // names the units library was imported as
+    open_units: bool, // a unit system was used, its units aren't known before running
+    scopes: Vec<HashMap<String, Binding>>,
+    functions: Rc<RefCell<HashMap<String, Rc<Function>>>>,
+    calls: Vec<(String, Option<TokenSpan>)>, // user functions being followed and where they were called
+    errors: Vec<Error>,
+}
+
+#[derive(Clone)]
+struct Binding {
+    dimension: Dimension,
+    declared: bool, // `unknown x: m` can only ever hold a length
+}
+
+impl DimensionChecker {
+    pub fn new(reporter: Rc<RefCell<ErrorReporter>>) -> Self {
+        Self {
+            reporter,
+            units: HashMap::new(),
+            units_modules: HashSet::new(),
+            open_units: false,
+            scopes: vec![HashMap::new()],
+            functions: Rc::new(RefCell::new(HashMap::new())),
+            calls: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Every dimension mismatch in a script, in the order they appear
+    pub fn check(mut self, statements: &[Stmt]) -> Vec<Error> {
+        self.statements(statements);
+        let mut errors = self.errors;
+        errors.sort_by_key(|e| (e.token.line, e.token.column));
+        errors
+    }
+
+    // One error per position, a function body is checked again for every call
+    fn report(&mut self, token: &TokenSpan, message: String) {
+        let duplicate = self.errors.iter().any(|e| e.token.line == token.line && e.token.column == token.column);
+        let message = match self.calls.last() {
+            Some((name, Some(site))) => format!("{}, in '{}' called at line {}", message, name, site.line),
+            _ => message,
+        };
+        if !duplicate {
+            let error = self.reporter.borrow_mut().error_with_kind(ErrorKind::Dimension, token.clone(), message);
+            self.errors.push(error);
+        }
+    }
+
+    // --- Scopes ---
+
+    fn define(&mut self, name: String, dimension: Dimension, declared: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, Binding { dimension, declared });
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(HashMap::new());
+        let result = f(self);
+        self.scopes.pop();
+        result
+    }
+
+    // --- Statements ---
+
+    // The dimension of the last statement, what a block or a function gives back
+    fn statements(&mut self, statements: &[Stmt]) -> Dimension {
+        let mut last = None;
+        for stmt in statements {
+            last = self.statement(stmt);
+        }
+        last
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Dimension {
+        match stmt {
+            Stmt::Import { path, alias } => {
+                if let Token::Identifier(name) = &path.token {
+                    self.import(name, alias.as_ref().map_or(name.clone(), TokenSpan::token_to_string));
+                }
+                None
+            }
+            Stmt::Let { name, initializer } | Stmt::Symbolic { name, initializer, .. } => {
+                let dimension = self.expression(initializer);
+                self.define(name.token_to_string(), dimension, false);
+                None
+            }
+            Stmt::Unknown { name, domain, unit } => {
+                let domain = domain.as_ref().and_then(|domain| self.expression(domain));
+                let dimension = match unit {
+                    Some(unit) => self.unit(unit),
+                    None => domain,
+                };
+                self.define(name.token_to_string(), dimension, unit.is_some());
+                None
+            }
+            Stmt::Assign { name, value } => {
+                let dimension = self.expression(value);
+                self.assign(name, dimension);
+                None
+            }
+            Stmt::SymbolicFunction { name, params, body } => {
+                let params = params.iter().map(|p| Parameter { name: p.clone(), default: None, unit: None }).collect();
+                self.declare_function(Function { name: name.clone(), params, returns: None, body: Body::Expression(body.clone()) });
+                None
+            }
+            Stmt::Function { name, params, returns, body, .. } => {
+                let body = Body::Statements(body.clone());
+                self.declare_function(Function { name: name.clone(), params: params.clone(), returns: returns.clone(), body });
+                None
+            }
+            Stmt::Expression { expression } => self.expression(expression),
+            Stmt::Block { statements } => self.scoped(|checker| checker.statements(statements)),
+            Stmt::If { condition, then_branch, elif_branch, else_branch, .. } => {
+                self.expression(condition);
+                self.scoped(|checker| checker.statement(then_branch));
+                for (condition, branch) in elif_branch {
+                    self.expression(condition);
+                    self.scoped(|checker| checker.statement(branch));
+                }
+                if let Some(branch) = else_branch {
+                    self.scoped(|checker| checker.statement(branch));
+                }
+                None
+            }
+            Stmt::Match { value, arms } => {
+                self.expression(value);
+                for (pattern, arm) in arms {
+                    self.expression(pattern);
+                    self.scoped(|checker| checker.statement(arm));
+                }
+                None
+            }
+            Stmt::TryCatch { try_block, catch_block } => {
+                self.scoped(|checker| checker.statement(try_block));
+                self.scoped(|checker| checker.statement(catch_block));
+                None
+            }
+            Stmt::Loop { body } => {
+                self.scoped(|checker| checker.statements(body));
+                None
+            }
+            Stmt::For { variable, iterable, body } => {
+                self.expression(iterable);
+                self.scoped(|checker| {
+                    checker.define(variable.token_to_string(), None, false);
+                    checker.statements(body);
+                });
+                None
+            }
+            Stmt::Panic | Stmt::Break | Stmt::Continue => None,
+        }
+    }
+
+    // A variable can change unit when it is assigned again, unless it was declared with one.
+    // Once it has held two units, or a unit after something that couldn't be told, later uses
+    // aren't checked, the assignment may sit in a branch or a loop that doesn't always run
+    fn assign(&mut self, name: &TokenSpan, dimension: Dimension) {
+        let key = name.token_to_string();
+        let Some(binding) = self.lookup(&key).cloned() else {
+            return;
+        };
+
+        match (&binding.dimension, &dimension) {
+            (Some(held), Some(new)) if held != new && binding.declared => {
+                let message = format!("'{}' was declared in {}, cannot assign {}", key, held, symbolic::describe(new));
+                self.report(name, message);
+                return;
+            }
+            (Some(held), Some(new)) if held == new => return,
+            _ if binding.declared => return,
+            _ => {}
+        }
+
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(binding) = scope.get_mut(&key) {
+                binding.dimension = None;
+                return;
+            }
+        }
+    }
+
+    fn declare_function(&mut self, function: Function) {
+        let name = function.name.token_to_string();
+        let function = Rc::new(function);
+        self.functions.borrow_mut().insert(name.clone(), function.clone());
+
+        // once with parameters in their annotated units, or any unit, for mismatches that don't
+        // depend on what is passed in
+        let params = function
+            .params
+            .iter()
+            .map(|param| (param.name.token_to_string(), param.unit.as_ref().and_then(|unit| self.unit(unit))))
+            .collect();
+        let result = self.enter(&name, None, &function, params);
+
+        let expected = function.returns.as_ref().and_then(|unit| Some((unit, self.unit(unit)?)));
+        if let (Some((written, expected)), Some(result)) = (expected, result) && expected != result {
+            let message = format!(
+                "The result of '{}' should be in {}, got {}",
+                name,
+                Value::format_unit_expr(written),
+                symbolic::describe(&result)
+            );
+            self.report(&function.name, message);
+        }
+    }
+
+    // --- Imports and units ---
+
+    fn import(&mut self, library: &str, name: String) {
+        if !units::LIBRARIES.contains(&library) {
+            return;
+        }
+        if library == "units" {
+            self.units_modules.insert(name);
+        }
+        let Some(register) = crate::libs::get_library_registry().get(library).copied() else {
+            return;
+        };
+
+        let mut env = Environment::new();
+        register(&mut env);
+        for name in env.names() {
+            if let Ok(Value::Quantity { unit, .. }) = env.get(&name) {
+                self.units.insert(name, unit.relative());
+            }
+        }
+    }
+
+    fn unit_named(&self, name: &str) -> Option<Unit> {
+        if let Some(unit) = self.units.get(name) {
+            return Some(unit.clone());
+        }
+        units::prefixed(name).find_map(|(_, rest)| self.units.get(rest).cloned())
+    }
+
+    fn unit(&mut self, unit: &UnitExpr) -> Dimension {
+        match unit {
+            UnitExpr::Name(name) => {
+                let key = name.token_to_string();
+                // a variable holding a quantity works as a unit too
+                if let Some(binding) = self.lookup(&key) {
+                    return binding.dimension.clone();
+                }
+                let unit = self.unit_named(&key);
+                if unit.is_none() && !self.units.is_empty() && !self.open_units {
+                    self.report(name, format!("Undefined unit '{}'", key));
+                }
+                unit
+            }
+            UnitExpr::Mul(left, right) => self.unit(left)?.checked_mul(&self.unit(right)?),
+            UnitExpr::Div(left, right) => self.unit(left)?.checked_div(&self.unit(right)?),
+            UnitExpr::Pow(base, exp) => self.unit(base)?.checked_pow(*exp),
+        }
+    }
+
+    // --- Expressions ---
+
+    fn expression(&mut self, expr: &Expr) -> Dimension {
+        match expr {
+            Expr::Literal { value } => match value.token {
+                Token::Number(_) => Some(Unit::dimensionless()),
+                _ => None,
+            },
+            Expr::Constant { value } => symbolic::value_dimension(value),
+            Expr::Grouping { expression } => self.expression(expression),
+            Expr::Unary { operator, right } => {
+                let dimension = self.expression(right);
+                if operator.token == Token::Minus { dimension } else { None }
+            }
+            Expr::Variable { name } => self.lookup(&name.token_to_string()).and_then(|binding| binding.dimension.clone()),
+            Expr::Quantity { value, unit } => {
+                let value = self.expression(value);
+                let unit = self.unit(unit)?;
+                value.unwrap_or_else(Unit::dimensionless).checked_mul(&unit)
+            }
+            Expr::Interval { min, max, bracket } => {
+                let low = self.expression(min);
+                let high = self.expression(max);
+                if let (Some(low), Some(high)) = (&low, &high) && low != high {
+                    let message = format!("Interval bounds in different units, {} and {}", low, high);
+                    self.report(bracket, message);
+                    return None;
+                }
+                low
+            }
+            Expr::Binary { left, operator, right } => {
+                let l = self.expression(left);
+                let r = self.expression(right);
+                let exponent = match right.as_ref() {
+                    Expr::Literal { value: TokenSpan { token: Token::Number(n), .. } } => Some(*n),
+                    _ => None,
+                };
+                match symbolic::combine_dimensions(&operator.token, l, r, exponent) {
+                    Ok(dimension) => dimension,
+                    Err(message) => {
+                        self.report(operator, format!("Unit mismatch: {}", message));
+                        None
+                    }
+                }
+            }
+            Expr::Block { statements } => self.scoped(|checker| checker.statements(statements)),
+            Expr::Given { bindings, statements } => self.scoped(|checker| {
+                for (name, value) in bindings {
+                    let dimension = checker.expression(value);
+                    checker.define(name.token_to_string(), dimension, false);
+                }
+                checker.statements(statements)
+            }),
+            Expr::Call { callee, paren, arguments } => self.call(callee, paren, arguments),
+            Expr::Get { object, .. } => {
+                self.expression(object);
+                None
+            }
+            Expr::Array { elements, .. } => {
+                for element in elements {
+                    self.expression(element);
+                }
+                None
+            }
+            Expr::Index { object, index, .. } => {
+                self.expression(object);
+                self.expression(index);
+                None
+            }
+            Expr::Postfix { .. } => None,
+        }
+    }
+
+    // Where a unit is expected unit names can be written on their own, see `Evaluator::eval_unit_argument`
+    fn unit_argument(&mut self, expr: &Expr) -> Dimension {
+        match expr {
+            Expr::Variable { name } if self.lookup(&name.token_to_string()).is_none() => {
+                self.unit(&UnitExpr::Name(name.clone()))
+            }
+            Expr::Grouping { expression } => self.unit_argument(expression),
+            Expr::Binary { left, operator, right } if matches!(operator.token, Token::Star | Token::Slash | Token::Caret) => {
+                let l = self.unit_argument(left);
+                let (r, exponent) = match (&operator.token, right.as_ref()) {
+                    (Token::Caret, Expr::Literal { value: TokenSpan { token: Token::Number(n), .. } }) => (None, Some(*n)),
+                    (Token::Caret, _) => (None, None),
+                    _ => (self.unit_argument(right), None),
+                };
+                symbolic::combine_dimensions(&operator.token, l, r, exponent).ok().flatten()
+            }
+            other => self.expression(other),
+        }
+    }
+
+    fn call(&mut self, callee: &Expr, paren: &TokenSpan, arguments: &[Expr]) -> Dimension {
+        let is_unit_argument = |i: usize| match callee {
+            Expr::Variable { name } => name.token_to_string() == "to" && i == 1,
+            Expr::Get { name, .. } => name.token_to_string() == "in" || (name.token_to_string() == "format" && i == 1),
+            _ => false,
+        };
+        let args: Vec<Dimension> = arguments
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| if is_unit_argument(i) { self.unit_argument(arg) } else { self.expression(arg) })
+            .collect();
+
+        match callee {
+            Expr::Variable { name } => {
+                let key = name.token_to_string();
+                let function = self.functions.borrow().get(&key).cloned();
+                match function {
+                    Some(function) if self.lookup(&key).is_none() => self.call_function(&key, paren, &function, args),
+                    _ if key == "to" => {
+                        self.conversion(paren, args.first().and_then(Option::as_ref), args.get(1).and_then(Option::as_ref))
+                    }
+                    _ => None,
+                }
+            }
+            Expr::Get { object, name } => {
+                let object_dimension = self.expression(object);
+                let method = name.token_to_string();
+                let is_math = matches!(object.as_ref(), Expr::Variable { name } if name.token_to_string() == "math");
+                let is_units = matches!(object.as_ref(), Expr::Variable { name } if self.units_modules.contains(&name.token_to_string()));
+                match method.as_str() {
+                    "in" => self.conversion(paren, object_dimension.as_ref(), args.first().and_then(Option::as_ref)),
+                    "define" if is_units => {
+                        if let (Some(Expr::Literal { value: TokenSpan { token: Token::String(unit), .. } }), Some(Some(dimension))) =
+                            (arguments.first(), args.get(1))
+                        {
+                            self.units.insert(unit.clone(), dimension.relative());
+                        }
+                        None
+                    }
+                    "dimension" if is_units => match arguments.first() {
+                        Some(Expr::Literal { value: TokenSpan { token: Token::String(name), .. } }) => {
+                            self.units.insert(name.clone(), Unit::base(name));
+                            Some(Unit::base(name))
+                        }
+                        _ => None,
+                    },
+                    "use" if is_units => {
+                        self.open_units = true;
+                        None
+                    }
+                    "sqrt" if is_math => args.first().cloned().flatten().and_then(|unit| unit.checked_root(2)),
+                    "abs" | "floor" | "round" | "min" | "max" | "hypot" if is_math => {
+                        args.first().cloned().flatten()
+                    }
+                    _ => None,
+                }
+            }
+            other => {
+                self.expression(other);
+                None
+            }
+        }
+    }
+
+    // `to(q, unit)` and `q.in(unit)` give a plain number, when both sides are in the same dimension
+    fn conversion(&mut self, paren: &TokenSpan, from: Option<&Unit>, to: Option<&Unit>) -> Dimension {
+        if let (Some(from), Some(to)) = (from, to) && from != to {
+            let message = format!("Cannot convert {} to {}", symbolic::describe(from), symbolic::describe(to));
+            self.report(paren, message);
+            return None;
+        }
+        Some(Unit::dimensionless())
+    }
+
+    // Checks the arguments of a call against the unit annotations of the function, then its
+    // body with the units of the arguments, so a parameter without one has the unit of whatever
+    // is passed in
+    fn call_function(&mut self, name: &str, paren: &TokenSpan, function: &Function, args: Vec<Dimension>) -> Dimension {
+        let returns = function.returns.as_ref().and_then(|unit| self.unit(unit));
+
+        let mut params = Vec::new();
+        for (i, param) in function.params.iter().enumerate() {
+            let given = match args.get(i) {
+                Some(dimension) => dimension.clone(),
+                None => param.default.as_ref().and_then(|default| self.expression(default)),
+            };
+            let annotated = param.unit.as_ref().and_then(|unit| Some((unit, self.unit(unit)?)));
+            if let (Some((written, expected)), Some(given)) = (&annotated, &given) && expected != given {
+                let message = format!(
+                    "Argument '{}' should be in {}, got {}",
+                    param.name.token_to_string(),
+                    Value::format_unit_expr(written),
+                    symbolic::describe(given)
+                );
+                self.report(paren, message);
+            }
+            params.push((param.name.token_to_string(), annotated.map(|(_, unit)| unit).or(given)));
+        }
+
+        if self.calls.len() >= MAX_DEPTH || self.calls.iter().any(|(call, _)| call == name) {
+            return returns;
+        }
+        let result = self.enter(name, Some(paren.clone()), function, params);
+        returns.or(result)
+    }
+
+    fn enter(&mut self, name: &str, site: Option<TokenSpan>, function: &Function, params: Vec<(String, Dimension)>) -> Dimension {
+        self.calls.push((name.to_string(), site));
+        let result = self.scoped(|checker| {
+            for (param, dimension) in params {
+                checker.define(param, dimension, false);
+            }
+            match &function.body {
+                Body::Statements(statements) => checker.statements(statements),
+                Body::Expression(expr) => checker.expression(expr),
+            }
+        });
+        self.calls.pop();
+        result
+    }
+}
@@ -1,12 +1,23 @@
 use std::collections::HashMap;
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use crate::core::units::Unit;
 use crate::core::value::Value;
 use crate::evaluator::builtins;
+use crate::evaluator::provenance::{self, Cause};
+use crate::libs::units;
+
+thread_local! {
+    static VERSIONS: Cell<u64> = const { Cell::new(0) };
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Environment {
     values: HashMap<String, Value>,
+    causes: HashMap<String, Rc<Cause>>, // provenance of the uncertain values, see why()
+    declarations: HashMap<String, Declaration>, // domain and unit of `unknown x: unit in [..]`
+    versions: HashMap<String, u64>, // changes on every assignment, see `version`
+    unit_libraries: HashMap<String, String>, // unit libraries by the name they were imported as
     pub enclosing: Option<Rc<RefCell<Environment>>>,
 }
 
@@ -14,16 +25,28 @@ impl Environment {
     pub fn new() -> Self {
         let mut env = Self {
             values: HashMap::new(),
+            causes: HashMap::new(),
+            declarations: HashMap::new(),
+            versions: HashMap::new(),
+            unit_libraries: HashMap::new(),
             enclosing: None,
         };
 
-        let defs: [(&str, crate::core::value::NativeFn); 15] = [
+        let defs: [(&str, crate::core::value::NativeFn); 23] = [
             ("print", builtins::print),
             ("write", builtins::write),
             ("input", builtins::input),
             ("num", builtins::num),
             ("str", builtins::str),
+            ("to", builtins::to),
             ("resolve", builtins::resolve),
+            ("refine", builtins::refine),
+            ("derivative", builtins::derivative),
+            ("sensitivity", builtins::sensitivity),
+            ("why", builtins::why),
+            ("graph", builtins::graph),
+            ("scenarios", builtins::scenarios),
+            ("table", builtins::table),
             ("certain", builtins::certain),
             ("impossible", builtins::impossible),
             ("possible", builtins::possible),
@@ -45,14 +68,130 @@ impl Environment {
     pub fn new_enclosed(enclosing: Rc<RefCell<Environment>>) -> Self {
         Self {
             values: HashMap::new(),
+            causes: HashMap::new(),
+            declarations: HashMap::new(),
+            versions: HashMap::new(),
+            unit_libraries: HashMap::new(),
             enclosing: Some(enclosing),
         }
     }
 
     pub fn define(&mut self, name: String, value: Value) {
+        self.define_traced(name, value, None);
+    }
+
+    pub fn define_traced(&mut self, name: String, value: Value, cause: Option<Rc<Cause>>) {
+        self.declarations.remove(&name);
+        self.store(name, value, cause);
+    }
+
+    /// Defines an unknown whose later values have to fit its declared domain and unit
+    pub fn declare(&mut self, name: String, value: Value, declaration: Declaration) {
+        self.define(name.clone(), value);
+        self.declarations.insert(name, declaration);
+    }
+
+    fn store(&mut self, name: String, value: Value, cause: Option<Rc<Cause>>) {
+        self.causes.remove(&name);
+        if let Some(cause) = cause && provenance::is_uncertain(&value) {
+            self.causes.insert(name.clone(), cause);
+        }
+        self.versions.insert(name.clone(), VERSIONS.with(|v| v.replace(v.get() + 1)));
         self.values.insert(name, value);
     }
 
+    /// A number that is different for every value a variable has held, in every scope,
+    /// so cached results can tell whether what they read has changed
+    pub fn version(&self, name: &str) -> Option<u64> {
+        if let Some(version) = self.versions.get(name) {
+            return Some(*version);
+        }
+        self.enclosing.as_ref().and_then(|enclosing| enclosing.borrow().version(name))
+    }
+
+    /// Names defined in this scope, not in the enclosing ones
+    pub fn names(&self) -> Vec<String> {
+        self.values.keys().cloned().collect()
+    }
+
+    /// Defines the module of a unit library, whatever name it was imported as its units can be
+    /// written after numbers, see `unit_libraries`
+    pub fn define_unit_library(&mut self, name: String, library: &str, module: Value) {
+        self.unit_libraries.insert(name.clone(), library.to_string());
+        self.define(name, module);
+    }
+
+    /// The imported unit libraries and their modules, from the innermost scope out and in the
+    /// order of `units::LIBRARIES` within a scope
+    pub fn unit_libraries(&self) -> Vec<(String, Rc<RefCell<Environment>>)> {
+        let mut libraries: Vec<_> = self
+            .unit_libraries
+            .iter()
+            .filter_map(|(name, library)| match self.values.get(name) {
+                Some(Value::Module(module)) => Some((library.clone(), module.clone())),
+                _ => None,
+            })
+            .collect();
+        libraries.sort_by_key(|(library, _)| units::LIBRARIES.iter().position(|name| name == library));
+        if let Some(enclosing) = &self.enclosing {
+            libraries.extend(enclosing.borrow().unit_libraries());
+        }
+        libraries
+    }
+
+    /// The unit library a variable holds the module of, `u` after `import units as u`
+    pub fn unit_library(&self, name: &str) -> Option<String> {
+        if self.values.contains_key(name) {
+            return match self.values.get(name) {
+                Some(Value::Module(_)) => self.unit_libraries.get(name).cloned(),
+                _ => None,
+            };
+        }
+        self.enclosing.as_ref().and_then(|enclosing| enclosing.borrow().unit_library(name))
+    }
+
+    /// The declared domain of an unknown, looked up in the same scope as its value
+    pub fn domain(&self, name: &str) -> Option<Value> {
+        if self.values.contains_key(name) {
+            return self.declarations.get(name).and_then(|declaration| declaration.domain.clone());
+        }
+        self.enclosing.as_ref().and_then(|enclosing| enclosing.borrow().domain(name))
+    }
+
+    /// Provenance of the value a variable holds, looked up in the same scope as the value
+    pub fn get_cause(&self, name: &str) -> Option<Rc<Cause>> {
+        if self.values.contains_key(name) {
+            return self.causes.get(name).cloned();
+        }
+        self.enclosing.as_ref().and_then(|enclosing| enclosing.borrow().get_cause(name))
+    }
+
+    /// Copies every scope from `env` up to the global one, and the modules they hold, as
+    /// `units.define` adds to a module. See `restore`
+    pub fn snapshot(env: &Rc<RefCell<Environment>>) -> Vec<(Rc<RefCell<Environment>>, Environment)> {
+        let mut scopes: Vec<(Rc<RefCell<Environment>>, Environment)> = Vec::new();
+        let mut pending = vec![env.clone()];
+        while let Some(scope) = pending.pop() {
+            if scopes.iter().any(|(saved, _)| Rc::ptr_eq(saved, &scope)) {
+                continue;
+            }
+            let saved = scope.borrow().clone();
+            pending.extend(saved.enclosing.clone());
+            pending.extend(saved.values.values().filter_map(|value| match value {
+                Value::Module(module) => Some(module.clone()),
+                _ => None,
+            }));
+            scopes.push((scope, saved));
+        }
+        scopes
+    }
+
+    pub fn restore(snapshot: Vec<(Rc<RefCell<Environment>>, Environment)>) {
+        for (scope, saved) in snapshot {
+            *scope.borrow_mut() = saved;
+        }
+    }
+
     pub fn get(&self, name: &str) -> Result<Value, String> {
         if let Some(value) = self.values.get(name) {
             return Ok(value.clone());
@@ -66,15 +205,66 @@ impl Environment {
     }
 
     pub fn assign(&mut self, name: &str, value: Value) -> Result<(), String> {
+        self.assign_traced(name, value, None)
+    }
+
+    pub fn assign_traced(&mut self, name: &str, value: Value, cause: Option<Rc<Cause>>) -> Result<(), String> {
         if self.values.contains_key(name) {
-            self.values.insert(name.to_string(), value);
+            if let Some(declaration) = self.declarations.get(name) {
+                declaration.check(name, &value)?;
+            }
+            self.store(name.to_string(), value, cause);
             return Ok(());
         }
 
         if let Some(ref enclosing) = self.enclosing {
-            return enclosing.borrow_mut().assign(name, value);
+            return enclosing.borrow_mut().assign_traced(name, value, cause);
         }
 
         Err(format!("Undefined variable '{}'", name))
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+    pub domain: Option<Value>, // an interval, in `unit` when there is one
+    pub unit: Option<Unit>,
+}
+
+impl Declaration {
+    fn check(&self, name: &str, value: &Value) -> Result<(), String> {
+        let (inner, unit) = match value {
+            Value::Quantity { value, unit } => (value.as_ref(), Some(unit)),
+            other => (other, None),
+        };
+
+        if unit != self.unit.as_ref() {
+            return Err(match &self.unit {
+                Some(expected) => format!("'{}' was declared in {}, cannot assign {}", name, expected, value),
+                None => format!("'{}' was declared without a unit, cannot assign {}", name, value),
+            });
+        }
+
+        let Some((low, high)) = self.domain.as_ref().and_then(bounds) else {
+            return Ok(());
+        };
+        let fits = match inner {
+            Value::Unknown | Value::Symbolic { .. } => true,
+            _ => bounds(inner).is_some_and(|(min, max)| low <= min && max <= high),
+        };
+        if !fits {
+            let domain = self.domain.as_ref().map(|d| d.to_string()).unwrap_or_default();
+            return Err(format!("{} is outside the declared domain {} of '{}'", value, domain, name));
+        }
+        Ok(())
+    }
+}
+
+fn bounds(value: &Value) -> Option<(f64, f64)> {
+    match value {
+        Value::Number(n) => Some((*n, *n)),
+        Value::Interval(min, max) => Some((*min, *max)),
+        Value::Quantity { value, .. } => bounds(value),
+        _ => None,
+    }
+}
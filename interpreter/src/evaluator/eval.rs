@@ -2,9 +2,14 @@ use crate::parser::ast::{Expr, IfPolicy, Stmt, UnitExpr};
 use crate::parser::lexer::{Token, TokenSpan};
 use crate::core::value::{Function, SKBool, Value};
 use crate::core::logic;
+use crate::core::rounding;
 use crate::core::units::Unit;
 use crate::core::error::{Error, ErrorReporter};
-use crate::evaluator::env::Environment;
+use crate::evaluator::env::{Declaration, Environment};
+use crate::evaluator::provenance::{self, Cause, Operand, Step};
+use crate::evaluator::symbolic;
+use crate::libs::units;
+use std::collections::HashMap;
 use std::rc::Rc;
 use std::cell::RefCell;
 
@@ -18,6 +23,19 @@ pub struct Evaluator {
     control_flow: ControlFlow,
     reporter: Rc<RefCell<ErrorReporter>>,
     safe_mode: bool,
+    cause: Option<Rc<Cause>>, // provenance of the last evaluated expression
+    argument_causes: Vec<Option<Rc<Cause>>>, // provenance of the arguments of the current call
+    resolving: Vec<String>, // quiet values being evaluated, to catch cycles
+    resolved: HashMap<u64, Resolved>, // by the version of the quiet variable
+}
+
+const MAX_RESOLVED: usize = 1024;
+
+// A quiet value that was already evaluated, valid while nothing it reads is reassigned
+struct Resolved {
+    value: Value,
+    cause: Option<Rc<Cause>>,
+    dependencies: Vec<(String, Option<u64>)>,
 }
 
 impl Evaluator {
@@ -31,6 +49,10 @@ impl Evaluator {
             control_flow: ControlFlow::None,
             reporter,
             safe_mode,
+            cause: None,
+            argument_causes: Vec::new(),
+            resolving: Vec::new(),
+            resolved: HashMap::new(),
         }
     }
 
@@ -38,6 +60,11 @@ impl Evaluator {
         self.safe_mode
     }
 
+    /// Where an argument of the native function being called came from, if it is uncertain
+    pub fn argument_cause(&self, index: usize) -> Option<Rc<Cause>> {
+        self.argument_causes.get(index).cloned().flatten()
+    }
+
     pub fn evaluate(&mut self, statements: Vec<Stmt>) -> Result<Value, Error> {
         let mut last_value = Value::None;
         for stmt in statements {
@@ -50,6 +77,35 @@ impl Evaluator {
         self.eval_expr(expr)
     }
 
+    /// Resolves a symbolic expression through the symbolic variables it uses. While some input is
+    /// unknown the result stays symbolic, with everything else already worked out
+    #[allow(clippy::result_large_err)]
+    pub fn resolve_expression(&mut self, expression: Expr) -> Result<Value, Error> {
+        let inputs = symbolic::collect_inputs(&self.env.borrow(), &expression);
+        if inputs.unknowns.is_empty() {
+            return self.evaluate_with_bindings(expression, inputs.formula_bindings());
+        }
+
+        let inlined = symbolic::inline_formulas(&expression, &inputs.formulas);
+        let partial = symbolic::partial_resolve(&inlined, &inputs.unknowns, &mut |e| self.evaluate_expression(e.clone()))?;
+        Ok(Value::Symbolic { expression: Box::new(partial), is_quiet: false })
+    }
+
+    /// Evaluates an expression with some variables temporarily rebound in a child scope
+    #[allow(clippy::result_large_err)]
+    pub fn evaluate_with_bindings(&mut self, expr: Expr, bindings: Vec<(String, Value)>) -> Result<Value, Error> {
+        let mut layer = Environment::new_enclosed(self.env.clone());
+        for (name, value) in bindings {
+            layer.define(name, value);
+        }
+
+        let previous = self.env.clone();
+        self.env = Rc::new(RefCell::new(layer));
+        let result = self.eval_expr(expr);
+        self.env = previous;
+        result
+    }
+
     pub fn error(&self, token: TokenSpan, msg: impl Into<String>) -> Error {
         self.reporter.borrow_mut().error(token, msg)
     }
@@ -111,10 +167,12 @@ impl Evaluator {
                                 lib_name.clone()
                             };
 
-                            self.env.borrow_mut().define(
-                                name, 
-                                Value::Module(Rc::new(RefCell::new(lib_env)))
-                            );
+                            let module = Value::Module(Rc::new(RefCell::new(lib_env)));
+                            if units::LIBRARIES.contains(&lib_name.as_str()) {
+                                self.env.borrow_mut().define_unit_library(name, lib_name, module);
+                            } else {
+                                self.env.borrow_mut().define(name, module);
+                            }
                         } else {
                             return Err(self.report_error(
                                 path.clone(),
@@ -202,13 +260,29 @@ impl Evaluator {
             }
             Stmt::Let { name, initializer } => {
                 let val = self.eval_expr(initializer)?;
+                let cause = self.cause.take();
                 if let Token::Identifier(n) = &name.token {
-                    self.env.borrow_mut().define(n.clone(), val);
+                    self.env.borrow_mut().define_traced(n.clone(), val, cause);
                 }
                 Ok(Value::None)
             }
+            Stmt::Unknown { name, domain: None, unit: None } => {
+                self.env.borrow_mut().define(name.token_to_string(), Value::Unknown);
+                Ok(Value::None)
+            }
+            Stmt::Unknown { name, domain, unit } => {
+                let (value, declaration) = self.declared_unknown(&name, domain, unit)?;
+                self.env.borrow_mut().declare(name.token_to_string(), value, declaration);
+                Ok(Value::None)
+            }
             Stmt::Symbolic { name, initializer, is_quiet } => {
                 if let Token::Identifier(n) = &name.token {
+                    if let Some(cycle) = symbolic::find_cycle(&self.env.borrow(), n, &initializer) {
+                        return Err(self.report_error(name, format!("Cycle in symbolic definitions: {}", cycle.join(" -> "))));
+                    }
+                    if let Err(msg) = self.symbolic_dimension(&initializer) {
+                        return Err(self.report_error(name.clone(), format!("Unit mismatch in symbolic '{}': {}", n, msg)));
+                    }
                     self.env.borrow_mut().define(n.clone(), Value::Symbolic {
                         expression: Box::new(initializer),
                         is_quiet,
@@ -218,13 +292,24 @@ impl Evaluator {
             }
             Stmt::Assign { name, value } => {
                 let val = self.eval_expr(value)?;
+                let cause = self.cause.take();
                 if let Token::Identifier(n) = &name.token {
-                    if let Err(msg) = self.env.borrow_mut().assign(n, val) {
+                    if let Err(msg) = self.env.borrow_mut().assign_traced(n, val, cause) {
                         return Err(self.report_error(name, msg));
                     }
                 }
                 Ok(Value::None)
             }
+            Stmt::SymbolicFunction { name, params, body } => {
+                let params: Vec<String> = params.iter().map(|p| p.token_to_string()).collect();
+                let n = name.token_to_string();
+                let hidden = symbolic::hide_parameters(&body, &params);
+                if let Some(cycle) = symbolic::find_cycle(&self.env.borrow(), &n, &hidden) {
+                    return Err(self.report_error(name, format!("Cycle in symbolic definitions: {}", cycle.join(" -> "))));
+                }
+                self.env.borrow_mut().define(n, Value::SymbolicFn { params, expression: Box::new(body) });
+                Ok(Value::None)
+            }
             Stmt::Panic => Err(self.report_error(
                 TokenSpan {
                     token: Token::Panic,
@@ -243,8 +328,8 @@ impl Evaluator {
                     Err(_) => self.eval_stmt(*catch_block.clone()),
                 }
             }
-            Stmt::Function { name, params, body, is_public } => {
-                let function = Value::Function(Function { params, body, closure: self.env.clone(), is_public });
+            Stmt::Function { name, params, returns, body, is_public } => {
+                let function = Value::Function(Box::new(Function { params, returns, body, closure: self.env.clone(), is_public }));
                 self.env.borrow_mut().define(name.token_to_string(), function);
                 Ok(Value::None)
             }
@@ -378,6 +463,7 @@ impl Evaluator {
         policy: IfPolicy,
     ) -> Result<Value, Error> {
         let cond_val = self.eval_expr(cond_expr)?;
+        let condition_cause = self.cause.take();
         let sk_bool = match cond_val {
             Value::Bool(b) => b,
             _ => {
@@ -410,7 +496,7 @@ impl Evaluator {
                 IfPolicy::Merge => {
                     let val_true = self.eval_stmt(body)?;
                     let val_false = self.eval_next_in_chain(remaining_elifs, else_branch, policy)?;
-                    self.merge_values(val_true, val_false)
+                    self.merge_values(val_true, val_false, condition_cause)
                 }
             },
         }
@@ -432,38 +518,46 @@ impl Evaluator {
     }
 
     // Not fully implemented
-    fn merge_values(&mut self, v1: Value, v2: Value) -> Result<Value, Error> {
-        match (v1, v2) {
-            (Value::Number(n1), Value::Number(n2)) => Ok(Value::Interval(n1.min(n2), n1.max(n2))),
-            (Value::Interval(l1, h1), Value::Interval(l2, h2)) => Ok(Value::Interval(l1.min(l2), h1.max(h2))),
+    fn merge_values(&mut self, v1: Value, v2: Value, condition: Option<Rc<Cause>>) -> Result<Value, Error> {
+        let merged = match (v1, v2) {
+            (Value::Number(n1), Value::Number(n2)) => Value::Interval(n1.min(n2), n1.max(n2)),
+            (Value::Interval(l1, h1), Value::Interval(l2, h2)) => Value::Interval(l1.min(l2), h1.max(h2)),
             (Value::Number(n), Value::Interval(l, h)) | (Value::Interval(l, h), Value::Number(n)) => {
-                Ok(Value::Interval(n.min(l), n.max(h)))
+                Value::Interval(n.min(l), n.max(h))
             }
-            (a, b) if a == b => Ok(a),
-            _ => Ok(Value::Unknown),
-        }
-    }
+            (a, b) if a == b => a,
+            _ => Value::Unknown,
+        };
 
-    fn value_to_token(&self, value: Value) -> Token {
-        match value {
-            Value::Number(n) => Token::Number(n),
-            Value::String(s) => Token::String(s),
-            Value::Bool(SKBool::True) => Token::True,
-            Value::Bool(SKBool::False) => Token::False,
-            Value::Bool(SKBool::Partial) => Token::Partial,
-            Value::Unknown => Token::Unknown,
-            Value::None => Token::None,
-            _ => Token::Unknown,
-        }
+        self.cause = provenance::is_uncertain(&merged)
+            .then(|| Cause::new(Step::Merge { result: merged.clone(), condition }));
+        Ok(merged)
     }
 
     fn eval_expr(&mut self, expr: Expr) -> Result<Value, Error> {
+        self.cause = None;
         match expr {
             Expr::Block { statements } => {
                 let new_env = Environment::new_enclosed(self.env.clone());
                 self.execute_block(statements, new_env)
             }
 
+            // The bindings live in a new layer, and every scope outside is restored afterwards
+            // so nothing assigned inside the block leaks out
+            Expr::Given { bindings, statements } => {
+                let mut layer = Environment::new_enclosed(self.env.clone());
+                for (name, initializer) in bindings {
+                    let value = self.eval_expr(initializer)?;
+                    layer.define_traced(name.token_to_string(), value, self.cause.take());
+                }
+
+                let snapshot = Environment::snapshot(&self.env);
+                let result = self.execute_block(statements, layer);
+                Environment::restore(snapshot);
+                result
+            }
+
+            Expr::Constant { value } => Ok(value),
             Expr::Literal { value } => match value.token {
                 Token::Number(n) => Ok(Value::Number(n)),
                 Token::String(s) => Ok(Value::String(s)),
@@ -486,12 +580,23 @@ impl Evaluator {
                     .get(name_str)
                     .map_err(|msg| self.report_error(name.clone(), msg))?;
 
-                match value {
-                    Value::Symbolic { expression, is_quiet: true } => {
-                        self.evaluate_expression(*expression)
+                let value = match value {
+                    Value::Symbolic { expression, is_quiet: true } => self.resolve_quiet(&name, name_str, *expression)?,
+                    _ => {
+                        self.cause = self.env.borrow().get_cause(name_str);
+                        value
                     }
-                    _ => Ok(value),
+                };
+
+                if provenance::is_uncertain(&value) {
+                    self.cause = Some(Cause::new(Step::Variable {
+                        name: name_str.clone(),
+                        value: value.clone(),
+                        line: name.line,
+                        origin: self.cause.take(),
+                    }));
                 }
+                Ok(value)
             }
 
             Expr::Postfix { name, operator } => {
@@ -600,7 +705,9 @@ impl Evaluator {
                 let low = self.eval_expr(*min)?;
                 let high = self.eval_expr(*max)?;
                 match (low, high) {
-                    (Value::Number(l), Value::Number(h)) => Ok(Value::Interval(l, h)),
+                    (Value::Number(l), Value::Number(h)) => {
+                        Ok(Value::Interval(rounding::literal_down(l), rounding::literal_up(h)))
+                    }
                     _ => Err(self.report_error(
                         bracket,
                         "Interval bounds must be numbers",
@@ -613,15 +720,23 @@ impl Evaluator {
                 operator,
                 right,
             } => {
-                let l_val = self.eval_expr(*left)?;
-                let r_val = self.eval_expr(*right)?;
-                self.apply_binary(l_val, operator, r_val)
+                let (l_name, r_name) = (Self::variable_name(&left), Self::variable_name(&right));
+                let l_val = self.eval_operand(*left)?;
+                let l_cause = self.cause.take();
+                let r_val = self.eval_operand(*right)?;
+                let r_cause = self.cause.take();
+                self.apply_binary(
+                    Operand { value: l_val, name: l_name, cause: l_cause },
+                    operator,
+                    Operand { value: r_val, name: r_name, cause: r_cause },
+                )
             }
 
             Expr::Unary { operator, right } => {
-                let val = self.eval_expr(*right)?;
+                let val = self.eval_operand(*right)?;
                 match (operator.token.clone(), val) {
                     (Token::Minus, Value::Number(n)) => Ok(Value::Number(-n)),
+                    (Token::Minus, Value::Interval(min, max)) => Ok(Value::Interval(-max, -min)),
                     (Token::Minus, Value::Quantity { value, unit }) => {
                         let negated = Value::Number(0.0).sub(value.as_ref())
                             .map_err(|e| self.report_error(operator.clone(), e.message))?;
@@ -635,7 +750,7 @@ impl Evaluator {
             Expr::Grouping { expression } => self.eval_expr(*expression),
             Expr::Quantity { value, unit } => {
                 let inner = self.eval_expr(*value)?;
-                let (parsed_unit, scale) = self.eval_unit_value(&unit)?;
+                let (parsed_unit, scale) = self.eval_written_unit(&unit)?;
                 let scaled_inner = if scale == 1.0 {
                     inner
                 } else {
@@ -643,8 +758,14 @@ impl Evaluator {
                         .mul(&Value::Number(scale))
                         .map_err(|e| self.report_error(self.unit_token(&unit), e.message))?
                 };
+                let absolute = match parsed_unit.offset() {
+                    Some(offset) => scaled_inner
+                        .add(&Value::Number(offset))
+                        .map_err(|e| self.report_error(self.unit_token(&unit), e.message))?,
+                    None => scaled_inner,
+                };
                 Ok(Value::Quantity {
-                    value: Box::new(scaled_inner),
+                    value: Box::new(absolute),
                     unit: parsed_unit,
                 })
             }
@@ -682,6 +803,14 @@ impl Evaluator {
                             obj.pop()
                                 .map_err(|e| self.report_error(name.clone(), e.message))
                         }
+                        "in" => {
+                            let [target] = arguments.as_slice() else {
+                                return Err(self.report_error(paren, "in() expects a unit, like d.in(km)"));
+                            };
+                            let target = self.eval_unit_argument(target.clone())?;
+                            obj.in_unit(&target)
+                                .map_err(|e| self.report_error(name.clone(), e.message))
+                        }
                         "reverse" => {
                             if !arguments.is_empty() {
                                 return Err(self.report_error(paren, "reverse() takes no arguments"));
@@ -692,7 +821,7 @@ impl Evaluator {
                         }
                         _ => {
                             let callee_val = self.eval_expr(*callee.clone())?;
-                            return self.call_function(callee_val, arguments, paren);
+                            return self.call_function(&callee, callee_val, arguments, paren);
                         }
                     };
 
@@ -710,8 +839,8 @@ impl Evaluator {
                     }
                 } else {
                     // Basic function call
-                    let callee_val = self.eval_expr(*callee)?;
-                    self.call_function(callee_val, arguments, paren)
+                    let callee_val = self.eval_expr(*callee.clone())?;
+                    self.call_function(&callee, callee_val, arguments, paren)
                 }
             }
 
@@ -749,7 +878,53 @@ impl Evaluator {
         }
     }
 
-    fn apply_binary(&mut self, left: Value, op: TokenSpan, right: Value) -> Result<Value, Error> {
+    // An unknown declared with a domain takes part in arithmetic as its domain until it is
+    // assigned, the variable itself stays unknown
+    #[allow(clippy::result_large_err)]
+    fn eval_operand(&mut self, expr: Expr) -> Result<Value, Error> {
+        let name = Self::variable_name(&expr);
+        let value = self.eval_expr(expr)?;
+        let is_unknown = match &value {
+            Value::Quantity { value, .. } => **value == Value::Unknown,
+            other => *other == Value::Unknown,
+        };
+        match name {
+            Some(name) if is_unknown => Ok(self.env.borrow().domain(&name).unwrap_or(value)),
+            _ => Ok(value),
+        }
+    }
+
+    fn variable_name(expr: &Expr) -> Option<String> {
+        match expr {
+            Expr::Variable { name: TokenSpan { token: Token::Identifier(n), .. } } => Some(n.clone()),
+            _ => None,
+        }
+    }
+
+    fn apply_binary(&mut self, left: Operand, op: TokenSpan, right: Operand) -> Result<Value, Error> {
+        let traced = left.cause.is_some()
+            || right.cause.is_some()
+            || provenance::is_uncertain(&left.value)
+            || provenance::is_uncertain(&right.value);
+        if !traced {
+            return self.binary_result(left.value, op, right.value);
+        }
+
+        let result = self.binary_result(left.value.clone(), op.clone(), right.value.clone())?;
+        if provenance::is_uncertain(&result) {
+            self.cause = Some(Cause::new(Step::Operation {
+                operator: Value::operator_symbol(&op.token).to_string(),
+                left,
+                right,
+                result: result.clone(),
+                line: op.line,
+            }));
+        }
+        Ok(result)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn binary_result(&mut self, left: Value, op: TokenSpan, right: Value) -> Result<Value, Error> {
         let operator = op.token.clone();
 
         match operator { // Pre-calculations for x - x, x / x and x * 0
@@ -772,10 +947,19 @@ impl Evaluator {
             _ => {}
         }
 
-        if left == Value::Unknown || right == Value::Unknown {
+        // a quantity plus or minus an unknown keeps its unit, see `Value::add`
+        let keeps_unit = matches!(operator, Token::Plus | Token::Minus)
+            && (matches!(left, Value::Quantity { .. }) || matches!(right, Value::Quantity { .. }));
+        if (left == Value::Unknown || right == Value::Unknown) && !keeps_unit {
             return Ok(Value::Unknown);
         }
 
+        if matches!(operator, Token::EqualEqual | Token::BangEqual)
+            && (matches!(left, Value::Symbolic { .. }) || matches!(right, Value::Symbolic { .. }))
+        {
+            return self.compare_symbolic(left, op, right);
+        }
+
         let is_symbolic = left.is_symbolic_or_unknown() || right.is_symbolic_or_unknown();
 
         let res: Result<Value, String> = match operator {
@@ -805,6 +989,152 @@ impl Evaluator {
         }
     }
 
+    // The value of `unknown x in domain: unit`, the domain itself when there is one so arithmetic
+    // keeps its bounds, otherwise an unknown carrying the unit
+    #[allow(clippy::result_large_err)]
+    fn declared_unknown(&mut self, name: &TokenSpan, domain: Option<Expr>, unit: Option<UnitExpr>) -> Result<(Value, Declaration), Error> {
+        let unit = match unit {
+            Some(unit) => Some(self.eval_written_unit(&unit)?),
+            None => None,
+        };
+
+        let domain = match domain {
+            Some(expr) => Some(self.eval_expr(expr)?),
+            None => None,
+        };
+
+        let domain = match (domain, &unit) {
+            (None, _) => None,
+            (Some(value @ (Value::Interval(..) | Value::Number(_))), Some((unit, scale))) => {
+                let offset = Value::Number(unit.offset().unwrap_or(0.0));
+                let scaled = value
+                    .mul(&Value::Number(*scale))
+                    .and_then(|v| v.add(&offset))
+                    .map_err(|e| self.report_error(name.clone(), e.message))?;
+                Some(Value::Quantity { value: Box::new(scaled), unit: unit.clone() })
+            }
+            (Some(value @ (Value::Interval(..) | Value::Number(_))), None) => Some(value),
+            (Some(Value::Quantity { value, unit: domain_unit }), unit) if matches!(value.as_ref(), Value::Interval(..) | Value::Number(_)) => {
+                if let Some((unit, _)) = unit && *unit != domain_unit {
+                    return Err(self.report_error(name.clone(), format!("The domain of '{}' is not in {}", name.token_to_string(), unit)));
+                }
+                Some(Value::Quantity { value, unit: domain_unit })
+            }
+            (Some(_), _) => {
+                return Err(self.report_error(name.clone(), "The domain of an unknown must be an interval"));
+            }
+        };
+
+        let unit = match &domain {
+            Some(Value::Quantity { unit, .. }) => Some(unit.clone()),
+            _ => unit.map(|(unit, _)| unit),
+        };
+
+        let value = match &unit {
+            Some(unit) => Value::Quantity { value: Box::new(Value::Unknown), unit: unit.clone() },
+            None => Value::Unknown,
+        };
+
+        Ok((value, Declaration { domain, unit }))
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn resolve_quiet(&mut self, span: &TokenSpan, name: &str, expression: Expr) -> Result<Value, Error> {
+        if let Some(start) = self.resolving.iter().position(|n| n == name) {
+            let mut cycle = self.resolving[start..].to_vec();
+            cycle.push(name.to_string());
+            return Err(self.report_error(span.clone(), format!("Cycle in symbolic definitions: {}", cycle.join(" -> "))));
+        }
+
+        let version = self.env.borrow().version(name);
+        if let Some(cached) = version.and_then(|v| self.resolved.get(&v)) {
+            let env = self.env.borrow();
+            if cached.dependencies.iter().all(|(dependency, seen)| env.version(dependency) == *seen) {
+                self.cause = cached.cause.clone();
+                return Ok(cached.value.clone());
+            }
+        }
+
+        self.resolving.push(name.to_string());
+        let result = self.evaluate_expression(expression.clone());
+        self.resolving.pop();
+        let value = result?;
+
+        let dependencies = symbolic::dependencies(&self.env.borrow(), &expression);
+        if let (Some(version), Some(dependencies)) = (version, dependencies) {
+            let env = self.env.borrow();
+            let dependencies = dependencies.into_iter().map(|d| (d.clone(), env.version(&d))).collect();
+            if self.resolved.len() >= MAX_RESOLVED {
+                self.resolved.clear();
+            }
+            self.resolved.insert(version, Resolved { value: value.clone(), cause: self.cause.clone(), dependencies });
+        }
+        Ok(value)
+    }
+
+    // The same formula is equal whatever its inputs are, otherwise the current values decide,
+    // and it stays partial while some input is unknown
+    #[allow(clippy::result_large_err)]
+    fn compare_symbolic(&mut self, left: Value, op: TokenSpan, right: Value) -> Result<Value, Error> {
+        let structural = left.compare(&right, &op.token).map_err(|e| self.report_error(op.clone(), e.message))?;
+        if structural != Value::Bool(SKBool::Partial) {
+            return Ok(structural);
+        }
+
+        let mut resolve = |value: Value| match value {
+            Value::Symbolic { expression, .. } => self.eval_expr(*expression),
+            value => Ok(value),
+        };
+        let (left, right) = (resolve(left)?, resolve(right)?);
+        if left.is_symbolic_or_unknown() || right.is_symbolic_or_unknown() {
+            return Ok(Value::Bool(SKBool::Partial));
+        }
+        left.compare(&right, &op.token).map_err(|e| self.report_error(op, e.message))
+    }
+
+    // A unit as it was written after a value, it keeps that name for printing
+    #[allow(clippy::result_large_err)]
+    fn eval_written_unit(&self, unit: &UnitExpr) -> Result<(Unit, f64), Error> {
+        let (parsed, scale) = self.eval_unit_value(unit)?;
+        let written = Value::format_unit_expr(unit);
+        if parsed.is_dimensionless() || written == parsed.to_string() {
+            return Ok((parsed, scale));
+        }
+        Ok((parsed.displayed_as(written, scale), scale))
+    }
+
+    // Unit names can be used as values once the units library is imported, `to(d, km)`
+    fn unit_named(&self, name: &str) -> Option<Value> {
+        let (unit, size) = match self.library_unit(name)? {
+            Value::Quantity { value, unit } => match *value {
+                Value::Number(n) => (unit, n),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let scale = size - unit.offset().unwrap_or(0.0);
+        Some(Value::Quantity { value: Box::new(Value::Number(size)), unit: unit.displayed_as(name.to_string(), scale) })
+    }
+
+    // A unit of the imported unit libraries, by its name or by an SI prefix and its name. Units
+    // with an offset, like degC, don't take prefixes
+    fn library_unit(&self, name: &str) -> Option<Value> {
+        let modules = self.env.borrow().unit_libraries();
+        let lookup = |name: &str| modules.iter().find_map(|(_, module)| module.borrow().get(name).ok());
+
+        if let Some(value) = lookup(name) {
+            return Some(value);
+        }
+
+        units::prefixed(name).find_map(|(factor, rest)| match lookup(rest)? {
+            Value::Quantity { value, unit } if unit.offset().is_none() => match *value {
+                Value::Number(n) => Some(Value::Quantity { value: Box::new(Value::Number(n * factor)), unit }),
+                _ => None,
+            },
+            _ => None,
+        })
+    }
+
     fn eval_unit_value(&self, unit: &UnitExpr) -> Result<(Unit, f64), Error> {
         match unit {
             UnitExpr::Name(name) => {
@@ -813,10 +1143,8 @@ impl Evaluator {
                     return self.unit_from_value(value, name.clone());
                 }
 
-                if let Ok(Value::Module(units_mod)) = self.env.borrow().get("units") {
-                    if let Ok(value) = units_mod.borrow().get(&unit_name) {
-                        return self.unit_from_value(value, name.clone());
-                    }
+                if let Some(value) = self.library_unit(&unit_name) {
+                    return self.unit_from_value(value, name.clone());
                 }
 
                 Err(self.report_error(
@@ -827,16 +1155,16 @@ impl Evaluator {
             UnitExpr::Mul(left, right) => {
                 let (l_unit, l_scale) = self.eval_unit_value(left)?;
                 let (r_unit, r_scale) = self.eval_unit_value(right)?;
-                Ok((l_unit.mul(&r_unit), l_scale * r_scale))
+                Ok((self.unit_exponents(unit, l_unit.checked_mul(&r_unit))?, l_scale * r_scale))
             }
             UnitExpr::Div(left, right) => {
                 let (l_unit, l_scale) = self.eval_unit_value(left)?;
                 let (r_unit, r_scale) = self.eval_unit_value(right)?;
-                Ok((l_unit.div(&r_unit), l_scale / r_scale))
+                Ok((self.unit_exponents(unit, l_unit.checked_div(&r_unit))?, l_scale / r_scale))
             }
             UnitExpr::Pow(base, exp) => {
                 let (base_unit, base_scale) = self.eval_unit_value(base)?;
-                Ok((base_unit.pow(*exp), base_scale.powi(*exp)))
+                Ok((self.unit_exponents(unit, base_unit.checked_pow(*exp))?, base_scale.powf(exp.as_f64())))
             }
         }
     }
@@ -844,7 +1172,10 @@ impl Evaluator {
     fn unit_from_value(&self, value: Value, name: TokenSpan) -> Result<(Unit, f64), Error> {
         match value {
             Value::Quantity { value, unit } => match value.as_ref() {
-                Value::Number(n) => Ok((unit, *n)),
+                Value::Number(n) => {
+                    let scale = *n - unit.offset().unwrap_or(0.0); // the zero of an offset scale isn't part of its size
+                    Ok((unit, scale))
+                }
                 _ => Err(self.report_error(name, "Unit definitions must be numeric")),
             },
             _ => Err(self.report_error(name, "Unit definitions must be quantities")),
@@ -860,6 +1191,12 @@ impl Evaluator {
         }
     }
 
+    // The unit a product or power of written units comes to, if its exponents still fit
+    #[allow(clippy::result_large_err)]
+    fn unit_exponents(&self, written: &UnitExpr, unit: Option<Unit>) -> Result<Unit, Error> {
+        unit.ok_or_else(|| self.report_error(self.unit_token(written), "Unit exponent is too large"))
+    }
+
     fn simplify_symbolic(expr: Expr) -> Expr {
         match expr {
             Expr::Binary { left, operator, right } => {
@@ -919,31 +1256,22 @@ impl Evaluator {
 
         let left_expr = match left {
             Value::Symbolic { expression, .. } => *expression,
-            _ => Expr::Literal {
-                value: TokenSpan {
-                    token: self.value_to_token(left),
-                    line: 0,
-                    column: 0,
-                },
-            },
+            value => symbolic::constant(value),
         };
 
         let right_expr = match right {
             Value::Symbolic { expression, .. } => *expression,
-            _ => Expr::Literal {
-                value: TokenSpan {
-                    token: self.value_to_token(right),
-                    line: 0,
-                    column: 0,
-                },
-            },
+            value => symbolic::constant(value),
         };
 
         let expression = Self::simplify_symbolic(Expr::Binary {
             left: Box::new(left_expr),
-            operator: op,
+            operator: op.clone(),
             right: Box::new(right_expr),
         });
+        if let Err(msg) = self.symbolic_dimension(&expression) {
+            return Err(self.report_error(op, format!("Unit mismatch: {}", msg)));
+        }
 
         Ok(Value::Symbolic {
             expression: Box::new(expression),
@@ -951,18 +1279,126 @@ impl Evaluator {
         })
     }
 
+    // Checks a value against a unit annotation, `fn f(v: km/h)`, and shows it in that unit.
+    // Unknowns pass, and symbolics do when their unit can't be told yet
+    #[allow(clippy::result_large_err)]
+    fn in_annotated_unit(&mut self, value: Value, unit: &UnitExpr, what: &str, paren: &TokenSpan) -> Result<Value, Error> {
+        let (expected, _) = self.eval_written_unit(unit)?;
+        let actual = match &value {
+            Value::Quantity { unit, .. } => Some(unit.clone()),
+            Value::Number(_) | Value::Interval(..) => Some(Unit::dimensionless()),
+            Value::Symbolic { expression, .. } => self.symbolic_dimension(expression).ok().flatten(),
+            Value::Unknown => None,
+            other => {
+                let message = format!("{} should be in {}, got '{}'", what, Value::format_unit_expr(unit), other);
+                return Err(self.report_error(paren.clone(), message));
+            }
+        };
+
+        match (value, actual) {
+            (value, Some(actual)) if actual != expected => {
+                let got = match value {
+                    Value::Quantity { .. } => value.to_string(),
+                    Value::Symbolic { .. } => format!("a formula in {}", actual),
+                    _ => format!("{}, a plain number", value),
+                };
+                let message = format!("{} should be in {}, got {}", what, Value::format_unit_expr(unit), got);
+                Err(self.report_error(paren.clone(), message))
+            }
+            // same dimension, shown in the annotated unit unless one is a temperature and the other a difference
+            (Value::Quantity { value, unit }, _) if unit.offset().is_some() == expected.offset().is_some() => {
+                Ok(Value::Quantity { value, unit: expected })
+            }
+            (value, _) => Ok(value),
+        }
+    }
+
+    // The unit a symbolic expression resolves to, see `symbolic::dimension`
+    fn symbolic_dimension(&self, expr: &Expr) -> Result<Option<Unit>, String> {
+        let unit_of = |unit: &UnitExpr| self.eval_unit_value(unit).ok().map(|(unit, _)| unit);
+        symbolic::dimension(&self.env.borrow(), expr, &unit_of)
+    }
+
     fn report_error(&self, token: TokenSpan, msg: impl Into<String>) -> Error {
         self.error(token, msg)
     }
 
-    fn call_function(&mut self, callee_val: Value, arguments: Vec<Expr>, paren: TokenSpan) -> Result<Value, Error> {
+    // A value given as a unit, where unit names can be written on their own, `to(d, km/h)`.
+    // Anywhere else a unit name is only a variable if one was defined
+    #[allow(clippy::result_large_err)]
+    fn eval_unit_argument(&mut self, expr: Expr) -> Result<Value, Error> {
+        match expr {
+            Expr::Variable { name } => {
+                let name_str = name.token_to_string();
+                if self.env.borrow().get(&name_str).is_ok() {
+                    return self.eval_expr(Expr::Variable { name });
+                }
+                self.unit_named(&name_str)
+                    .ok_or_else(|| self.report_error(name, format!("Undefined unit '{}'", name_str)))
+            }
+            Expr::Grouping { expression } => self.eval_unit_argument(*expression),
+            Expr::Binary { left, operator, right } if matches!(operator.token, Token::Star | Token::Slash | Token::Caret) => {
+                let left = self.eval_unit_argument(*left)?;
+                let right = match operator.token {
+                    Token::Caret => self.eval_expr(*right)?,
+                    _ => self.eval_unit_argument(*right)?,
+                };
+                self.binary_result(left, operator, right)
+            }
+            other => self.eval_expr(other),
+        }
+    }
+
+    // The argument that is a unit, the target of `to()` and of `units.format()`
+    fn unit_argument(&self, callee: &Expr) -> Option<usize> {
+        match callee {
+            Expr::Variable { name } if name.token_to_string() == "to" => Some(1),
+            Expr::Get { object, name } if name.token_to_string() == "format" => match object.as_ref() {
+                Expr::Variable { name } if self.env.borrow().unit_library(&name.token_to_string()).as_deref() == Some("units") => Some(1),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn call_function(&mut self, callee: &Expr, callee_val: Value, arguments: Vec<Expr>, paren: TokenSpan) -> Result<Value, Error> {
+        let unit_argument = self.unit_argument(callee);
         let mut eval_args = Vec::new();
-        for arg in &arguments {
-            eval_args.push(self.eval_expr(arg.clone())?);
+        let mut causes = Vec::new();
+        for (i, arg) in arguments.iter().enumerate() {
+            let mut value = match unit_argument {
+                Some(index) if index == i => self.eval_unit_argument(arg.clone())?,
+                _ => self.eval_expr(arg.clone())?,
+            };
+            causes.push(self.cause.take());
+
+            // An unknown argument is passed as its expression, so the result can still name it
+            let unknown = matches!(&value, Value::Quantity { value, .. } if **value == Value::Unknown);
+            if matches!(callee_val, Value::SymbolicFn { .. }) && (value == Value::Unknown || unknown) {
+                value = Value::Symbolic { expression: Box::new(arg.clone()), is_quiet: false };
+            }
+            eval_args.push(value);
         }
 
+        let result = self.call_traced(callee_val, eval_args, causes, paren)?;
+        if !provenance::is_uncertain(&result) {
+            self.cause = None; // left over from the function body
+        }
+        Ok(result)
+    }
+
+    /// Calls a function value with already evaluated arguments, used by native libraries
+    #[allow(clippy::result_large_err)]
+    pub fn call_value(&mut self, callee_val: Value, eval_args: Vec<Value>, paren: TokenSpan) -> Result<Value, Error> {
+        self.call_traced(callee_val, eval_args, Vec::new(), paren)
+    }
+
+    // causes: provenance of each argument, parameters keep it so why() works inside functions too
+    #[allow(clippy::result_large_err)]
+    fn call_traced(&mut self, callee_val: Value, eval_args: Vec<Value>, causes: Vec<Option<Rc<Cause>>>, paren: TokenSpan) -> Result<Value, Error> {
         match callee_val {
             Value::NativeFn(func) => {
+                self.argument_causes = causes;
                 match func(eval_args, paren.clone(), self) {
                     Ok(v) => Ok(v),
                     Err(mut e) => {
@@ -991,7 +1427,16 @@ impl Evaluator {
                         ));
                     };
 
-                    call_env.define(param.name.token_to_string(), value);
+                    let value = match &param.unit {
+                        Some(unit) => {
+                            let what = format!("Argument '{}'", param.name.token_to_string());
+                            self.in_annotated_unit(value, unit, &what, &paren)?
+                        }
+                        None => value,
+                    };
+
+                    let cause = causes.get(i).cloned().flatten();
+                    call_env.define_traced(param.name.token_to_string(), value, cause);
                 }
 
                 if eval_args.len() > func.params.len() {
@@ -1005,7 +1450,32 @@ impl Evaluator {
                     ));
                 }
 
-                self.execute_block(func.body.clone(), call_env)
+                let result = self.execute_block(func.body.clone(), call_env)?;
+                match &func.returns {
+                    Some(unit) => self.in_annotated_unit(result, unit, "The result", &paren),
+                    None => Ok(result),
+                }
+            }
+            Value::SymbolicFn { params, expression } => {
+                if eval_args.len() != params.len() {
+                    return Err(self.report_error(
+                        paren,
+                        format!("Expected {} args, got {}", params.len(), eval_args.len()),
+                    ));
+                }
+
+                let arguments: Vec<(String, Expr)> = params
+                    .into_iter()
+                    .zip(eval_args)
+                    .map(|(param, value)| match value {
+                        Value::Symbolic { expression, .. } => (param, Expr::Grouping { expression }),
+                        value => (param, symbolic::constant(value)),
+                    })
+                    .collect();
+                let body = symbolic::substitute(&expression, &|name| {
+                    arguments.iter().find(|(param, _)| param == name).map(|(_, argument)| argument.clone())
+                });
+                self.resolve_expression(body)
             }
             _ => Err(self.report_error(
                 paren,
@@ -1,3 +1,6 @@
 pub mod env;
 pub mod eval;
-pub mod builtins;
\ No newline at end of file
+pub mod builtins;
+pub mod symbolic;
+pub mod provenance;
+pub mod dimensions;
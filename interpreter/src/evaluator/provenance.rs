@@ -0,0 +1,169 @@
+use crate::core::value::{SKBool, Value};
+use std::rc::Rc;
+
+// Provenance: where an uncertain value (an interval, unknown or partial) came from.
+// The evaluator keeps the cause of the last expression next to its value, and every variable
+// keeps the cause of the value it holds, so why() can follow the chain back to the inputs
+
+const MAX_SHOWN: usize = 6;
+const MAX_KEPT: usize = 32; // older steps are cut, a loop would otherwise grow the chain forever
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cause {
+    step: Step,
+    depth: usize,
+    truncated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    /// A variable holding an uncertain value, with how that value was made if it is known
+    Variable { name: String, value: Value, line: usize, origin: Option<Rc<Cause>> },
+    /// A binary operation with at least one uncertain operand
+    Operation { operator: String, left: Operand, right: Operand, result: Value, line: usize },
+    /// Both branches of an `if -> merge` ran because the condition was partial
+    Merge { result: Value, condition: Option<Rc<Cause>> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operand {
+    pub value: Value,
+    pub name: Option<String>, // when the operand is a plain variable
+    pub cause: Option<Rc<Cause>>,
+}
+
+/// Values that are worth explaining, anything else is known exactly
+pub fn is_uncertain(value: &Value) -> bool {
+    match value {
+        Value::Interval(..) | Value::Unknown | Value::Bool(SKBool::Partial) => true,
+        Value::Quantity { value, .. } => is_uncertain(value),
+        _ => false,
+    }
+}
+
+/// What why() prints for a value and the cause it was made with, if any
+pub fn explain(value: &Value, cause: Option<&Cause>) -> Vec<String> {
+    match cause {
+        _ if !is_uncertain(value) => vec![format!("{} is known exactly", value)],
+        Some(cause) => cause.explain(),
+        None => vec![format!("{} was given that way", value)],
+    }
+}
+
+impl Cause {
+    pub fn new(step: Step) -> Rc<Cause> {
+        let depth = 1 + step.links().iter().map(|c| c.depth).max().unwrap_or(0);
+        let cause = Cause { step, depth, truncated: false };
+        if depth <= MAX_KEPT {
+            return Rc::new(cause);
+        }
+        // keep a copy of the steps why() can show, and let go of the rest
+        Rc::new(cause.pruned(MAX_SHOWN))
+    }
+
+    fn pruned(&self, keep: usize) -> Cause {
+        let truncated = self.truncated || keep == 0;
+        let mut step = self.step.clone();
+        for link in step.links_mut() {
+            *link = match link.take() {
+                Some(cause) if !truncated => Some(Rc::new(cause.pruned(keep - 1))),
+                _ => None,
+            };
+        }
+        let depth = 1 + step.links().iter().map(|c| c.depth).max().unwrap_or(0);
+        Cause { step, depth, truncated }
+    }
+
+    fn line(&self) -> usize {
+        match &self.step {
+            Step::Variable { line, .. } | Step::Operation { line, .. } => *line,
+            Step::Merge { condition, .. } => condition.as_ref().map_or(0, |c| c.line()),
+        }
+    }
+
+    /// One line per step of the chain, indented by depth
+    pub fn explain(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        self.explain_into(0, &mut lines);
+        lines
+    }
+
+    fn explain_into(&self, depth: usize, lines: &mut Vec<String>) {
+        let indent = "  ".repeat(depth);
+        if depth >= MAX_SHOWN {
+            lines.push(format!("{}...", indent));
+            return;
+        }
+
+        match &self.step {
+            Step::Variable { name, value, origin, .. } => match origin {
+                Some(origin) => {
+                    lines.push(format!("{}`{}` is {}", indent, name, value));
+                    origin.explain_into(depth + 1, lines);
+                }
+                None if self.truncated => lines.push(format!("{}`{}` is {}", indent, name, value)),
+                None => lines.push(format!("{}`{}` is {}, it was given that way", indent, name, value)),
+            },
+            Step::Operation { operator, left, right, result, line } => {
+                let verb = match (result, operator.as_str()) {
+                    (Value::Bool(SKBool::Partial), ">" | "<" | ">=" | "<=" | "==" | "!=") => "overlaps",
+                    _ => operator.as_str(),
+                };
+                lines.push(format!(
+                    "{}{} because {} {} {} at line {}",
+                    indent,
+                    result,
+                    describe(left),
+                    verb,
+                    describe(right),
+                    line
+                ));
+                for operand in [left, right] {
+                    if let Some(cause) = &operand.cause && cause.has_origin() {
+                        cause.explain_into(depth + 1, lines);
+                    }
+                }
+            }
+            Step::Merge { result, condition } => {
+                lines.push(format!("{}{} because both branches of an if were merged at line {}", indent, result, self.line()));
+                if let Some(condition) = condition {
+                    condition.explain_into(depth + 1, lines);
+                }
+            }
+        }
+
+        if self.truncated {
+            lines.push(format!("{}  ...", indent));
+        }
+    }
+
+    // Plain inputs are already named in the line above, only derived values need their own lines
+    fn has_origin(&self) -> bool {
+        self.truncated || !matches!(self.step, Step::Variable { origin: None, .. })
+    }
+}
+
+impl Step {
+    fn links(&self) -> Vec<&Rc<Cause>> {
+        match self {
+            Step::Variable { origin, .. } => origin.iter().collect(),
+            Step::Operation { left, right, .. } => left.cause.iter().chain(right.cause.iter()).collect(),
+            Step::Merge { condition, .. } => condition.iter().collect(),
+        }
+    }
+
+    fn links_mut(&mut self) -> Vec<&mut Option<Rc<Cause>>> {
+        match self {
+            Step::Variable { origin, .. } => vec![origin],
+            Step::Operation { left, right, .. } => vec![&mut left.cause, &mut right.cause],
+            Step::Merge { condition, .. } => vec![condition],
+        }
+    }
+}
+
+fn describe(operand: &Operand) -> String {
+    match &operand.name {
+        Some(name) => format!("`{}` {}", name, operand.value),
+        None => operand.value.to_string(),
+    }
+}
@@ -0,0 +1,805 @@
+use crate::core::error::Error;
+use crate::core::units::{Ratio, Unit};
+use crate::core::value::{NativeFn, SKBool, Value};
+use crate::evaluator::env::Environment;
+use crate::libs::math;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use crate::parser::ast::{Expr, UnitExpr};
+use crate::parser::lexer::{Token, TokenSpan};
+
+// Helpers to look inside symbolic expressions
+
+/// What a symbolic expression depends on, following other symbolic variables it uses
+#[derive(Debug, Default)]
+pub struct SymbolicInputs {
+    pub intervals: Vec<(String, Value)>, // intervals and interval quantities
+    pub unknowns: Vec<String>,
+    pub formulas: Vec<(String, Expr)>, // symbolic variables the expression goes through
+}
+
+impl SymbolicInputs {
+    /// Bindings that make every symbolic variable in the chain evaluate right away
+    pub fn formula_bindings(&self) -> Vec<(String, Value)> {
+        self.formulas
+            .iter()
+            .map(|(name, expr)| {
+                (name.clone(), Value::Symbolic { expression: Box::new(expr.clone()), is_quiet: true })
+            })
+            .collect()
+    }
+}
+
+pub fn collect_inputs(env: &Environment, expr: &Expr) -> SymbolicInputs {
+    let mut inputs = SymbolicInputs::default();
+    let mut seen = Vec::new();
+    collect_into(env, expr, &mut inputs, &mut seen);
+    inputs
+}
+
+fn collect_into(env: &Environment, expr: &Expr, inputs: &mut SymbolicInputs, seen: &mut Vec<String>) {
+    for name in free_variables(expr) {
+        if seen.contains(&name) {
+            continue;
+        }
+        seen.push(name.clone());
+
+        match env.get(&name) {
+            Ok(value) if value.bisect().is_some() => inputs.intervals.push((name, value)),
+            Ok(Value::Unknown) => inputs.unknowns.push(name),
+            Ok(Value::Quantity { value, .. }) if *value == Value::Unknown => inputs.unknowns.push(name),
+            Ok(Value::Symbolic { expression, .. }) => {
+                collect_into(env, &expression, inputs, seen);
+                inputs.formulas.push((name, *expression));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Every variable an expression reads, following the symbolic variables it goes through.
+/// None when it calls something, as a call can read anything or give a new value every time
+pub fn dependencies(env: &Environment, expr: &Expr) -> Option<Vec<String>> {
+    let mut names = Vec::new();
+    collect_dependencies(env, expr, &mut names).then_some(names)
+}
+
+fn collect_dependencies(env: &Environment, expr: &Expr, names: &mut Vec<String>) -> bool {
+    if !is_pure(expr) {
+        return false;
+    }
+    for name in free_variables(expr) {
+        if names.contains(&name) {
+            continue;
+        }
+        names.push(name.clone());
+        if let Ok(Value::Symbolic { expression, .. }) = env.get(&name) && !collect_dependencies(env, &expression, names) {
+            return false;
+        }
+    }
+    true
+}
+
+fn is_pure(expr: &Expr) -> bool {
+    match expr {
+        Expr::Call { .. } | Expr::Postfix { .. } | Expr::Block { .. } | Expr::Given { .. } => false,
+        Expr::Binary { left, right, .. } => is_pure(left) && is_pure(right),
+        Expr::Interval { min, max, .. } => is_pure(min) && is_pure(max),
+        Expr::Index { object, index, .. } => is_pure(object) && is_pure(index),
+        Expr::Grouping { expression: inner }
+        | Expr::Unary { right: inner, .. }
+        | Expr::Get { object: inner, .. }
+        | Expr::Quantity { value: inner, .. } => is_pure(inner),
+        Expr::Array { elements, .. } => elements.iter().all(is_pure),
+        Expr::Literal { .. } | Expr::Constant { .. } | Expr::Variable { .. } => true,
+    }
+}
+
+/// The chain of symbolic variables that leads from `name` back to itself, if defining `name`
+/// as `expr` would make one
+pub fn find_cycle(env: &Environment, name: &str, expr: &Expr) -> Option<Vec<String>> {
+    let mut path = vec![name.to_string()];
+    let mut visited = Vec::new();
+    cycle_from(env, name, expr, &mut path, &mut visited)
+}
+
+fn cycle_from(env: &Environment, name: &str, expr: &Expr, path: &mut Vec<String>, visited: &mut Vec<String>) -> Option<Vec<String>> {
+    for var in free_variables(expr) {
+        if var == name {
+            let mut cycle = path.clone();
+            cycle.push(var);
+            return Some(cycle);
+        }
+        if visited.contains(&var) {
+            continue;
+        }
+        visited.push(var.clone());
+        let body = match env.get(&var) {
+            Ok(Value::Symbolic { expression, .. }) => Some(*expression),
+            Ok(Value::SymbolicFn { params, expression }) => Some(hide_parameters(&expression, &params)),
+            _ => None,
+        };
+        if let Some(expression) = body {
+            path.push(var);
+            if let Some(cycle) = cycle_from(env, name, &expression, path, visited) {
+                return Some(cycle);
+            }
+            path.pop();
+        }
+    }
+    None
+}
+
+/// The body of a symbolic function without its parameters, what is left are the variables it
+/// reads from the scope it is called in
+pub fn hide_parameters(expr: &Expr, params: &[String]) -> Expr {
+    substitute(expr, &|name| params.iter().any(|p| p == name).then(|| number(0.0)))
+}
+
+/// The body of a symbolic function with the symbolic variables it reads inlined,
+/// a parameter is never mistaken for an outer variable with the same name
+pub fn inline_body(env: &Environment, expr: &Expr, params: &[String]) -> Expr {
+    let formulas: Vec<(String, Expr)> = collect_inputs(env, &hide_parameters(expr, params))
+        .formulas
+        .into_iter()
+        .filter(|(name, _)| !params.contains(name))
+        .collect();
+    inline_formulas(expr, &formulas)
+}
+
+/// Variable names used by an expression, in order of appearance
+pub fn free_variables(expr: &Expr) -> Vec<String> {
+    let mut names = Vec::new();
+    walk_variables(expr, &mut names);
+    names
+}
+
+fn walk_variables(expr: &Expr, names: &mut Vec<String>) {
+    match expr {
+        Expr::Variable { name } | Expr::Postfix { name, .. } => {
+            if let Token::Identifier(n) = &name.token && !names.contains(n) {
+                names.push(n.clone());
+            }
+        }
+        Expr::Binary { left, right, .. } => {
+            walk_variables(left, names);
+            walk_variables(right, names);
+        }
+        Expr::Grouping { expression } => walk_variables(expression, names),
+        Expr::Unary { right, .. } => walk_variables(right, names),
+        Expr::Interval { min, max, .. } => {
+            walk_variables(min, names);
+            walk_variables(max, names);
+        }
+        Expr::Call { callee, arguments, .. } => {
+            walk_variables(callee, names);
+            for arg in arguments {
+                walk_variables(arg, names);
+            }
+        }
+        Expr::Get { object, .. } => walk_variables(object, names),
+        Expr::Quantity { value, .. } => walk_variables(value, names),
+        Expr::Array { elements, .. } => {
+            for element in elements {
+                walk_variables(element, names);
+            }
+        }
+        Expr::Index { object, index, .. } => {
+            walk_variables(object, names);
+            walk_variables(index, names);
+        }
+        Expr::Given { bindings, .. } => {
+            for (_, value) in bindings {
+                walk_variables(value, names);
+            }
+        }
+        Expr::Literal { .. } | Expr::Constant { .. } | Expr::Block { .. } => {}
+    }
+}
+
+/// Rebuilds an expression replacing the variables for which `replace` returns something
+pub fn substitute(expr: &Expr, replace: &dyn Fn(&str) -> Option<Expr>) -> Expr {
+    let sub = |e: &Expr| Box::new(substitute(e, replace));
+
+    match expr {
+        Expr::Variable { name } => {
+            if let Token::Identifier(n) = &name.token && let Some(replacement) = replace(n) {
+                return replacement;
+            }
+            expr.clone()
+        }
+        Expr::Binary { left, operator, right } => Expr::Binary {
+            left: sub(left),
+            operator: operator.clone(),
+            right: sub(right),
+        },
+        Expr::Grouping { expression } => Expr::Grouping { expression: sub(expression) },
+        Expr::Unary { operator, right } => Expr::Unary { operator: operator.clone(), right: sub(right) },
+        Expr::Interval { min, max, bracket } => Expr::Interval {
+            min: sub(min),
+            max: sub(max),
+            bracket: bracket.clone(),
+        },
+        Expr::Call { callee, paren, arguments } => Expr::Call {
+            callee: sub(callee),
+            paren: paren.clone(),
+            arguments: arguments.iter().map(|a| substitute(a, replace)).collect(),
+        },
+        Expr::Get { object, name } => Expr::Get { object: sub(object), name: name.clone() },
+        Expr::Quantity { value, unit } => Expr::Quantity { value: sub(value), unit: unit.clone() },
+        Expr::Array { elements, bracket } => Expr::Array {
+            elements: elements.iter().map(|e| substitute(e, replace)).collect(),
+            bracket: bracket.clone(),
+        },
+        Expr::Index { object, index, bracket } => Expr::Index {
+            object: sub(object),
+            index: sub(index),
+            bracket: bracket.clone(),
+        },
+        Expr::Literal { .. }
+        | Expr::Constant { .. }
+        | Expr::Postfix { .. }
+        | Expr::Block { .. }
+        | Expr::Given { .. } => expr.clone(),
+    }
+}
+
+/// Replaces the symbolic variables an expression goes through with their formulas
+pub fn inline_formulas(expr: &Expr, formulas: &[(String, Expr)]) -> Expr {
+    inline_with_stack(expr, formulas, &[])
+}
+
+fn inline_with_stack(expr: &Expr, formulas: &[(String, Expr)], stack: &[String]) -> Expr {
+    substitute(expr, &|name| {
+        if stack.iter().any(|n| n == name) {
+            return None; // self reference, leave the variable as is
+        }
+        let (_, formula) = formulas.iter().find(|(n, _)| n == name)?;
+        let mut inner_stack = stack.to_vec();
+        inner_stack.push(name.to_string());
+        let inlined = inline_with_stack(formula, formulas, &inner_stack);
+        Some(Expr::Grouping { expression: Box::new(inlined) })
+    })
+}
+
+/// Evaluates every part of an expression that doesn't depend on an unknown, so resolving
+/// `2 + b * [1..3]` with `b` unknown gives back `2 + b * [1..3]` instead of `unknown`
+#[allow(clippy::result_large_err)]
+pub fn partial_resolve(expr: &Expr, unknowns: &[String], resolve: &mut dyn FnMut(&Expr) -> Result<Value, Error>) -> Result<Expr, Error> {
+    if !free_variables(expr).iter().any(|name| unknowns.contains(name)) {
+        return Ok(constant(resolve(expr)?));
+    }
+
+    let mut partial = |e: &Expr| partial_resolve(e, unknowns, resolve).map(Box::new);
+    Ok(match expr {
+        Expr::Grouping { expression } => *partial(expression)?, // binary nodes print their own parentheses
+        Expr::Binary { left, operator, right } => Expr::Binary {
+            left: partial(left)?,
+            operator: operator.clone(),
+            right: partial(right)?,
+        },
+        Expr::Unary { operator, right } => Expr::Unary { operator: operator.clone(), right: partial(right)? },
+        Expr::Quantity { value, unit } => Expr::Quantity { value: partial(value)?, unit: unit.clone() },
+        Expr::Call { callee, paren, arguments } => Expr::Call {
+            callee: callee.clone(),
+            paren: paren.clone(),
+            arguments: arguments
+                .iter()
+                .map(|a| partial_resolve(a, unknowns, resolve))
+                .collect::<Result<_, _>>()?,
+        },
+        _ => expr.clone(), // the unknown variables themselves, and anything that can't be split
+    })
+}
+
+// --- Dimensions ---
+
+/// The unit a symbolic expression will have once it is resolved, read from its leaves: the
+/// quantities it was written with, the values of its variables and the units of typed
+/// unknowns. None when a part can't be told, like a call or an unknown without a unit.
+/// Adding or comparing parts of different dimensions is an error, before anything is known
+pub fn dimension(env: &Environment, expr: &Expr, unit_of: &dyn Fn(&UnitExpr) -> Option<Unit>) -> Result<Option<Unit>, String> {
+    dimension_of(env, expr, unit_of, &mut Vec::new())
+}
+
+fn dimension_of(env: &Environment, expr: &Expr, unit_of: &dyn Fn(&UnitExpr) -> Option<Unit>, seen: &mut Vec<String>) -> Result<Option<Unit>, String> {
+    let plain = Some(Unit::dimensionless());
+    Ok(match expr {
+        Expr::Literal { value: TokenSpan { token: Token::Number(_), .. } } => plain,
+        Expr::Interval { min, .. } => dimension_of(env, min, unit_of, seen)?,
+        Expr::Constant { value } => value_dimension(value),
+        Expr::Quantity { unit, .. } => unit_of(unit),
+        Expr::Grouping { expression } => dimension_of(env, expression, unit_of, seen)?,
+        Expr::Unary { operator, right } if operator.token == Token::Minus => dimension_of(env, right, unit_of, seen)?,
+        Expr::Variable { name } => {
+            let name = name.token_to_string();
+            match env.get(&name) {
+                Ok(Value::Symbolic { expression, .. }) if !seen.contains(&name) => {
+                    seen.push(name);
+                    let dimension = dimension_of(env, &expression, unit_of, seen);
+                    seen.pop();
+                    dimension?
+                }
+                Ok(value) => value_dimension(&value),
+                Err(_) => None,
+            }
+        }
+        Expr::Binary { left, operator, right } => {
+            let l = dimension_of(env, left, unit_of, seen)?;
+            let r = dimension_of(env, right, unit_of, seen)?;
+            combine_dimensions(&operator.token, l, r, as_number(right))?
+        }
+        _ => None,
+    })
+}
+
+/// The unit of `left op right` from the units of its sides, None when it can't be told.
+/// `exponent` is the right side of `^` when it is a plain number
+pub fn combine_dimensions(operator: &Token, left: Option<Unit>, right: Option<Unit>, exponent: Option<f64>) -> Result<Option<Unit>, String> {
+    Ok(match (operator, left, right) {
+        (Token::Star, Some(l), Some(r)) => l.checked_mul(&r),
+        (Token::Slash, Some(l), Some(r)) => l.checked_div(&r),
+        (Token::Caret, Some(l), _) if l.is_dimensionless() => Some(l),
+        (Token::Caret, Some(l), _) => exponent.and_then(Ratio::from_f64).and_then(|exp| l.checked_pow(exp)),
+        (Token::Plus | Token::Minus | Token::Modulo, Some(l), Some(r)) if l != r => {
+            return Err(format!("cannot {} {} and {}", verb(operator), describe(&l), describe(&r)));
+        }
+        (Token::Plus | Token::Minus | Token::Modulo, l, r) => l.or(r),
+        (
+            Token::Greater | Token::GreaterEqual | Token::Less | Token::LessEqual | Token::EqualEqual | Token::BangEqual,
+            Some(l),
+            Some(r),
+        ) if l != r => {
+            return Err(format!("cannot compare {} and {}", describe(&l), describe(&r)));
+        }
+        _ => None,
+    })
+}
+
+pub fn value_dimension(value: &Value) -> Option<Unit> {
+    match value {
+        Value::Number(_) | Value::Interval(..) => Some(Unit::dimensionless()),
+        Value::Quantity { unit, .. } => Some(unit.clone()),
+        _ => None,
+    }
+}
+
+fn verb(token: &Token) -> &'static str {
+    match token {
+        Token::Plus => "add",
+        Token::Minus => "subtract",
+        _ => "take the remainder of",
+    }
+}
+
+/// A unit in an error message, `m/s` or `a plain number`
+pub fn describe(unit: &Unit) -> String {
+    match unit.is_dimensionless() {
+        true => "a plain number".to_string(),
+        false => unit.to_string(),
+    }
+}
+
+// --- Canonical form ---
+
+/// A symbolic expression without source positions or grouping, the operands of commutative
+/// operators sorted and `a > b` written as `b < a`, so formulas that only differ in how they
+/// were written compare and hash the same. Use it as the key when symbolics go in a map.
+/// `+` also joins strings, so its operands are only sorted when they are surely numbers
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Canonical {
+    Number(u64), // the bits, with -0 folded into 0
+    Interval(u64, u64),
+    Text(String), // strings, booleans, unknown, and values that only compare by how they print
+    Variable(String),
+    Quantity(Box<Canonical>, String),
+    Operation(String, Vec<Canonical>),
+    Call(Box<Canonical>, Vec<Canonical>),
+}
+
+pub fn canonical(expr: &Expr) -> Canonical {
+    match expr {
+        Expr::Grouping { expression } => canonical(expression),
+        Expr::Literal { value } => match &value.token {
+            Token::Number(n) => canonical_number(*n),
+            Token::String(text) => Canonical::Text(format!("{:?}", text)),
+            token => Canonical::Text(format!("{:?}", token)),
+        },
+        Expr::Constant { value } => canonical_value(value),
+        Expr::Variable { name } => Canonical::Variable(name.token_to_string()),
+        Expr::Binary { left, operator, right } => {
+            let symbol = Value::operator_symbol(&operator.token);
+            match operator.token {
+                Token::Plus | Token::Star | Token::And | Token::Or => {
+                    let mut operands = Vec::new();
+                    flatten(expr, &operator.token, &mut operands);
+                    let commutes = operator.token != Token::Plus || operands.iter().all(|operand| is_numeric(operand));
+                    let mut operands: Vec<Canonical> = operands.into_iter().map(canonical).collect();
+                    if commutes {
+                        operands.sort();
+                    }
+                    Canonical::Operation(symbol.to_string(), operands)
+                }
+                Token::EqualEqual | Token::BangEqual => {
+                    let mut operands = vec![canonical(left), canonical(right)];
+                    operands.sort();
+                    Canonical::Operation(symbol.to_string(), operands)
+                }
+                Token::Greater => Canonical::Operation("<".to_string(), vec![canonical(right), canonical(left)]),
+                Token::GreaterEqual => Canonical::Operation("<=".to_string(), vec![canonical(right), canonical(left)]),
+                _ => Canonical::Operation(symbol.to_string(), vec![canonical(left), canonical(right)]),
+            }
+        }
+        Expr::Unary { operator, right } => {
+            let symbol = if operator.token == Token::Bang { "!" } else { "neg" };
+            Canonical::Operation(symbol.to_string(), vec![canonical(right)])
+        }
+        Expr::Interval { min, max, .. } => Canonical::Operation("..".to_string(), vec![canonical(min), canonical(max)]),
+        Expr::Quantity { value, unit } => Canonical::Quantity(Box::new(canonical(value)), Value::format_unit_expr(unit)),
+        Expr::Call { callee, arguments, .. } => {
+            Canonical::Call(Box::new(canonical(callee)), arguments.iter().map(canonical).collect())
+        }
+        Expr::Get { object, name } => Canonical::Operation(format!(".{}", name.token_to_string()), vec![canonical(object)]),
+        Expr::Array { elements, .. } => Canonical::Operation("[]".to_string(), elements.iter().map(canonical).collect()),
+        Expr::Index { object, index, .. } => {
+            Canonical::Operation("index".to_string(), vec![canonical(object), canonical(index)])
+        }
+        Expr::Postfix { name, operator } => {
+            Canonical::Operation(format!("{:?}", operator.token), vec![Canonical::Variable(name.token_to_string())])
+        }
+        Expr::Block { .. } | Expr::Given { .. } => Canonical::Text(format!("{:?}", expr)), // blocks keep their positions
+    }
+}
+
+/// Same formula up to positions, grouping and the order of commutative operands
+pub fn same_formula(a: &Expr, b: &Expr) -> bool {
+    canonical(a) == canonical(b)
+}
+
+// a + (b + c) and (a + b) + c become one `+` with three operands
+fn flatten<'a>(expr: &'a Expr, token: &Token, operands: &mut Vec<&'a Expr>) {
+    match expr {
+        Expr::Grouping { expression } => flatten(expression, token, operands),
+        Expr::Binary { left, operator, right } if operator.token == *token => {
+            flatten(left, token, operands);
+            flatten(right, token, operands);
+        }
+        _ => operands.push(expr),
+    }
+}
+
+// Whether an expression can only give a number, an interval or a quantity. A variable could
+// hold a string, so it isn't
+fn is_numeric(expr: &Expr) -> bool {
+    match expr {
+        Expr::Grouping { expression } => is_numeric(expression),
+        Expr::Literal { value } => matches!(value.token, Token::Number(_)),
+        Expr::Constant { value } => matches!(value, Value::Number(_) | Value::Interval(..) | Value::Quantity { .. }),
+        Expr::Interval { .. } | Expr::Quantity { .. } => true,
+        Expr::Unary { operator, .. } => operator.token == Token::Minus,
+        Expr::Binary { left, operator, right } => match operator.token {
+            Token::Star | Token::Slash | Token::Minus | Token::Caret | Token::Modulo => true,
+            Token::Plus => is_numeric(left) && is_numeric(right),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn canonical_number(n: f64) -> Canonical {
+    Canonical::Number(if n == 0.0 { 0.0f64.to_bits() } else { n.to_bits() })
+}
+
+fn canonical_value(value: &Value) -> Canonical {
+    match value {
+        Value::Number(n) => canonical_number(*n),
+        Value::Interval(min, max) => Canonical::Interval(min.to_bits(), max.to_bits()),
+        Value::Quantity { value, unit } => Canonical::Quantity(Box::new(canonical_value(value)), unit.to_string()),
+        other => Canonical::Text(other.to_string()),
+    }
+}
+
+// Hashing goes through the canonical form, expressions that are equal also hash the same
+impl Hash for Expr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        canonical(self).hash(state);
+    }
+}
+
+// --- Graphviz export ---
+
+/// Renders an expression as a DOT graph, `resolve` gives the enclosure shown at each node.
+/// Values flow along the edges, from the inputs down to the result
+#[allow(clippy::result_large_err)]
+pub fn dot_graph(expr: &Expr, formulas: &[(String, Expr)], resolve: &mut dyn FnMut(&Expr) -> Result<Value, Error>) -> Result<String, Error> {
+    let mut graph = DotGraph { formulas, lines: Vec::new(), nodes: 0, variables: HashMap::new(), resolve };
+    let root = graph.node(expr)?;
+    graph.lines.push(format!("  {} [penwidth=2];", root));
+
+    let mut dot = String::from("digraph {\n  node [fontname=\"monospace\"];\n");
+    for line in graph.lines {
+        dot.push_str(&line);
+        dot.push('\n');
+    }
+    dot.push('}');
+    Ok(dot)
+}
+
+struct DotGraph<'a> {
+    formulas: &'a [(String, Expr)],
+    lines: Vec<String>,
+    nodes: usize,
+    variables: HashMap<String, String>, // a variable used twice is drawn once, with two edges
+    resolve: &'a mut dyn FnMut(&Expr) -> Result<Value, Error>,
+}
+
+impl DotGraph<'_> {
+    #[allow(clippy::result_large_err)]
+    fn add(&mut self, label: &str, expr: &Expr, style: &str) -> Result<String, Error> {
+        let id = format!("n{}", self.nodes);
+        self.nodes += 1;
+        let value = (self.resolve)(expr)?;
+        let text = if label.is_empty() { value.to_string() } else { format!("{}\n= {}", label, value) };
+        let text = text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+        self.lines.push(format!("  {} [label=\"{}\"{}];", id, text, style));
+        Ok(id)
+    }
+
+    fn edges(&mut self, from: &[String], to: &str) {
+        for id in from {
+            self.lines.push(format!("  {} -> {};", id, to));
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn node(&mut self, expr: &Expr) -> Result<String, Error> {
+        match expr {
+            Expr::Grouping { expression } => self.node(expression),
+            Expr::Variable { name } => {
+                let Token::Identifier(n) = &name.token else { return self.add("?", expr, "") };
+                if let Some(id) = self.variables.get(n) {
+                    return Ok(id.clone());
+                }
+
+                let id = match self.formulas.iter().find(|(formula, _)| formula == n) {
+                    Some((_, formula)) => {
+                        let inner = self.node(formula)?;
+                        let id = self.add(&format!("{} (symbolic)", n), expr, ", shape=box, style=rounded")?;
+                        self.edges(&[inner], &id);
+                        id
+                    }
+                    None => self.add(n, expr, ", shape=box")?,
+                };
+                self.variables.insert(n.clone(), id.clone());
+                Ok(id)
+            }
+            Expr::Binary { left, operator, right } => {
+                let children = [self.node(left)?, self.node(right)?];
+                let id = self.add(Value::operator_symbol(&operator.token), expr, "")?;
+                self.edges(&children, &id);
+                Ok(id)
+            }
+            Expr::Unary { operator, right } => {
+                let child = self.node(right)?;
+                let label = if operator.token == Token::Bang { "!" } else { "-" };
+                let id = self.add(label, expr, "")?;
+                self.edges(&[child], &id);
+                Ok(id)
+            }
+            Expr::Call { callee, arguments, .. } => {
+                let mut children = Vec::new();
+                for argument in arguments {
+                    children.push(self.node(argument)?);
+                }
+                let name = callee_name(callee).unwrap_or_else(|| "call".to_string());
+                let id = self.add(&format!("{}()", name), expr, "")?;
+                self.edges(&children, &id);
+                Ok(id)
+            }
+            _ => self.add("", expr, ", shape=plaintext"), // literals and anything else show their value
+        }
+    }
+}
+
+// --- Differentiation ---
+
+fn span(token: Token) -> TokenSpan {
+    TokenSpan { token, line: 0, column: 0 }
+}
+
+pub fn number(n: f64) -> Expr {
+    Expr::Literal { value: span(Token::Number(n)) }
+}
+
+/// A value as an expression leaf, a literal when it has one and the value itself otherwise
+pub fn constant(value: Value) -> Expr {
+    let token = match value {
+        Value::Number(n) => Token::Number(n),
+        Value::String(s) => Token::String(s),
+        Value::Bool(SKBool::True) => Token::True,
+        Value::Bool(SKBool::False) => Token::False,
+        Value::Bool(SKBool::Partial) => Token::Partial,
+        Value::Unknown => Token::Unknown,
+        Value::None => Token::None,
+        value => return Expr::Constant { value },
+    };
+    Expr::Literal { value: span(token) }
+}
+
+fn as_number(expr: &Expr) -> Option<f64> {
+    match expr {
+        Expr::Literal { value: TokenSpan { token: Token::Number(n), .. } } => Some(*n),
+        Expr::Grouping { expression } => as_number(expression),
+        _ => None,
+    }
+}
+
+fn binary(left: Expr, token: Token, right: Expr) -> Expr {
+    Expr::Binary { left: Box::new(left), operator: span(token), right: Box::new(right) }
+}
+
+fn group(expr: Expr) -> Expr {
+    match expr {
+        Expr::Literal { .. }
+        | Expr::Constant { .. }
+        | Expr::Variable { .. }
+        | Expr::Grouping { .. }
+        | Expr::Binary { .. } // prints its own parentheses
+        | Expr::Call { .. } => expr,
+        _ => Expr::Grouping { expression: Box::new(expr) },
+    }
+}
+
+// Smart constructors, they fold constants so derivatives stay readable
+fn add(left: Expr, right: Expr) -> Expr {
+    match (as_number(&left), as_number(&right)) {
+        (Some(a), Some(b)) => number(a + b),
+        (Some(0.0), _) => right,
+        (_, Some(0.0)) => left,
+        _ => binary(left, Token::Plus, right),
+    }
+}
+
+fn sub(left: Expr, right: Expr) -> Expr {
+    match (as_number(&left), as_number(&right)) {
+        (Some(a), Some(b)) => number(a - b),
+        (_, Some(0.0)) => left,
+        (Some(0.0), _) => Expr::Unary { operator: span(Token::Minus), right: Box::new(group(right)) },
+        _ => binary(left, Token::Minus, group(right)),
+    }
+}
+
+fn mul(left: Expr, right: Expr) -> Expr {
+    match (as_number(&left), as_number(&right)) {
+        (Some(a), Some(b)) => number(a * b),
+        (Some(0.0), _) | (_, Some(0.0)) => number(0.0),
+        (Some(1.0), _) => right,
+        (_, Some(1.0)) => left,
+        _ => binary(group(left), Token::Star, group(right)),
+    }
+}
+
+fn div(left: Expr, right: Expr) -> Expr {
+    match (as_number(&left), as_number(&right)) {
+        (Some(0.0), _) => number(0.0),
+        (_, Some(1.0)) => left,
+        _ => binary(group(left), Token::Slash, group(right)),
+    }
+}
+
+fn pow(base: Expr, exponent: f64) -> Expr {
+    if exponent == 1.0 {
+        return base;
+    }
+    binary(group(base), Token::Caret, number(exponent))
+}
+
+fn call_like(original: &Expr, function: &str, argument: Expr) -> Expr {
+    match original {
+        Expr::Call { callee, paren, .. } => {
+            let callee = match callee.as_ref() {
+                Expr::Get { object, name } => Expr::Get {
+                    object: object.clone(),
+                    name: TokenSpan { token: Token::Identifier(function.to_string()), ..name.clone() },
+                },
+                _ => Expr::Variable { name: span(Token::Identifier(function.to_string())) },
+            };
+            Expr::Call { callee: Box::new(callee), paren: paren.clone(), arguments: vec![argument] }
+        }
+        _ => argument,
+    }
+}
+
+fn callee_name(callee: &Expr) -> Option<String> {
+    match callee {
+        Expr::Get { name, .. } | Expr::Variable { name } => match &name.token {
+            Token::Identifier(n) => Some(n.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// The native function a callee like `math.sin` or `sin` is bound to in `env`
+fn native_callee(env: &Environment, callee: &Expr) -> Option<NativeFn> {
+    let value = match callee {
+        Expr::Variable { name } => env.get(&name.token_to_string()).ok()?,
+        Expr::Get { object, name } => match object.as_ref() {
+            Expr::Variable { name: module } => match env.get(&module.token_to_string()).ok()? {
+                Value::Module(module) => module.borrow().get(&name.token_to_string()).ok()?,
+                _ => return None,
+            },
+            _ => return None,
+        },
+        _ => return None,
+    };
+    match value {
+        Value::NativeFn(function) => Some(function),
+        _ => None,
+    }
+}
+
+fn is_native(env: &Environment, callee: &Expr, native: NativeFn) -> bool {
+    native_callee(env, callee).is_some_and(|function| std::ptr::fn_addr_eq(function, native))
+}
+
+/// Derivative of an expression with respect to `var`, None when some part can't be differentiated.
+/// Calls are only differentiated when `env` binds them to the math library, a user `fn sin(x)` isn't
+pub fn derivative(expr: &Expr, var: &str, env: &Environment) -> Option<Expr> {
+    if !free_variables(expr).iter().any(|n| n == var) {
+        return Some(match expr {
+            Expr::Quantity { unit, .. } => Expr::Quantity { value: Box::new(number(0.0)), unit: unit.clone() },
+            _ => number(0.0),
+        });
+    }
+
+    match expr {
+        Expr::Variable { .. } => Some(number(1.0)),
+        Expr::Grouping { expression } => derivative(expression, var, env),
+        Expr::Unary { operator, right } if operator.token == Token::Minus => {
+            Some(sub(number(0.0), derivative(right, var, env)?))
+        }
+        Expr::Binary { left, operator, right } => {
+            let (l, r) = (left.as_ref().clone(), right.as_ref().clone());
+            match operator.token {
+                Token::Plus => Some(add(derivative(&l, var, env)?, derivative(&r, var, env)?)),
+                Token::Minus => Some(sub(derivative(&l, var, env)?, derivative(&r, var, env)?)),
+                Token::Star => {
+                    let (dl, dr) = (derivative(&l, var, env)?, derivative(&r, var, env)?);
+                    Some(add(mul(dl, r), mul(l, dr)))
+                }
+                Token::Slash => {
+                    let (dl, dr) = (derivative(&l, var, env)?, derivative(&r, var, env)?);
+                    Some(div(sub(mul(dl, r.clone()), mul(l, dr)), pow(r, 2.0)))
+                }
+                Token::Caret => {
+                    let n = as_number(&r)?;
+                    Some(mul(mul(number(n), pow(l.clone(), n - 1.0)), derivative(&l, var, env)?))
+                }
+                _ => None,
+            }
+        }
+        Expr::Call { callee, arguments, .. } if arguments.len() == 1 => {
+            let u = arguments[0].clone();
+            let du = derivative(&u, var, env)?;
+            // `math.sin` becomes `math.cos`, which has to be the math one too
+            let derived = |name: &str, native: NativeFn| {
+                let call = call_like(expr, name, u.clone());
+                match &call {
+                    Expr::Call { callee, .. } if is_native(env, callee, native) => Some(call),
+                    _ => None,
+                }
+            };
+            let outer = match native_callee(env, callee)? {
+                f if std::ptr::fn_addr_eq(f, math::sqrt as NativeFn) => div(number(1.0), mul(number(2.0), derived("sqrt", math::sqrt)?)),
+                f if std::ptr::fn_addr_eq(f, math::sin as NativeFn) => derived("cos", math::cos)?,
+                f if std::ptr::fn_addr_eq(f, math::cos as NativeFn) => sub(number(0.0), derived("sin", math::sin)?),
+                f if std::ptr::fn_addr_eq(f, math::exp as NativeFn) => derived("exp", math::exp)?,
+                f if std::ptr::fn_addr_eq(f, math::ln as NativeFn) => div(number(1.0), u),
+                _ => return None,
+            };
+            Some(mul(outer, du))
+        }
+        _ => None,
+    }
+}
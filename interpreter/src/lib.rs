@@ -11,14 +11,18 @@ pub mod libs;
 use crate::parser::lexer::{Lexer, Token, TokenSpan};
 use crate::parser::parser::Parser;
 use crate::evaluator::eval::Evaluator;
+use crate::evaluator::dimensions::DimensionChecker;
 use crate::evaluator::env::Environment;
+use crate::evaluator::provenance;
 use crate::core::value::Value;
+use crate::core::rounding;
 use crate::core::error::{Error, ErrorReporter, Warning};
 
 pub struct SKInterpreter {
     env: Rc<RefCell<Environment>>,
     reporter: Rc<RefCell<ErrorReporter>>,
     safe_mode: bool,
+    rigorous: bool,
 }
 
 impl SKInterpreter {
@@ -31,13 +35,27 @@ impl SKInterpreter {
             env: Rc::new(RefCell::new(Environment::new())),
             reporter: Rc::new(RefCell::new(ErrorReporter::new())),
             safe_mode, // Dont really like this solution but its the easiest for now...
+            rigorous: false,
         }
     }
 
+    /// Interval bounds are rounded outward so results always enclose the real value
+    pub fn rigorous(mut self, rigorous: bool) -> Self {
+        self.rigorous = rigorous;
+        self
+    }
+
     pub fn take_warnings(&mut self) -> Vec<Warning> {
         self.reporter.borrow_mut().take_warnings()
     }
 
+    /// What `why(name)` would print for a variable, None when it isn't defined
+    pub fn why(&self, name: &str) -> Option<Vec<String>> {
+        let env = self.env.borrow();
+        let value = env.get(name).ok()?;
+        Some(provenance::explain(&value, env.get_cause(name).as_deref()))
+    }
+
     pub fn execute(&mut self, source: &Path) -> Result<Value, Error> {
         let raw = fs::read_to_string(source).map_err(|e| {
             self.reporter
@@ -56,8 +74,48 @@ impl SKInterpreter {
         self.execute_named("<repl>".to_string(), source)
     }
 
+    /// Dimension mismatches in a script, found without running it. Syntax errors are the Err
+    #[allow(clippy::result_large_err)]
+    pub fn check_dimensions(&mut self, source: &Path) -> Result<Vec<Error>, Error> {
+        let raw = fs::read_to_string(source).map_err(|e| {
+            self.reporter
+                .borrow_mut()
+                .error(TokenSpan {
+                    token: Token::None,
+                    line: 0,
+                    column: 0,
+                }, format!("{}", e))
+        })?;
+
+        self.check_named(source.display().to_string(), raw)
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn check_dimensions_string(&mut self, source: String) -> Result<Vec<Error>, Error> {
+        self.check_named("<repl>".to_string(), source)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn check_named(&mut self, name: String, source: String) -> Result<Vec<Error>, Error> {
+        let previous = self.reporter.borrow_mut().set_source(name, source.clone());
+
+        let result = (|| {
+            let mut lexer = Lexer::new(source, self.reporter.clone());
+            let tokens = lexer.tokenize()?;
+
+            let mut parser = Parser::new(tokens, self.reporter.clone());
+            let ast = parser.parse()?;
+
+            Ok(DimensionChecker::new(self.reporter.clone()).check(&ast))
+        })();
+
+        self.reporter.borrow_mut().restore_source(previous);
+        result
+    }
+
     fn execute_named(&mut self, name: String, source: String) -> Result<Value, Error> {
         let previous = self.reporter.borrow_mut().set_source(name, source.clone());
+        let previous_rounding = rounding::set_rigorous(self.rigorous);
 
         let result = (|| {
             let mut lexer = Lexer::new(source, self.reporter.clone());
@@ -71,6 +129,7 @@ impl SKInterpreter {
         })();
 
         self.reporter.borrow_mut().restore_source(previous);
+        rounding::set_rigorous(previous_rounding);
 
         result
     }
@@ -1,8 +1,11 @@
 use crate::core::value::Value;
+use crate::core::rounding;
+use crate::core::units::Unit;
 use crate::evaluator::env::Environment;
 use crate::evaluator::eval::Evaluator;
 use crate::core::error::Error;
 use crate::parser::lexer::TokenSpan;
+use std::f64::consts::PI;
 
 // The SK Math library!
 
@@ -26,9 +29,16 @@ pub fn register(env: &mut Environment) {
     env.define("deg".into(), Value::NativeFn(deg));
     env.define("rad".into(), Value::NativeFn(rad));
     env.define("atan2".into(), Value::NativeFn(atan2));
-
-    env.define("PI".into(), Value::Number(std::f64::consts::PI));
-    env.define("E".into(), Value::Number(std::f64::consts::E));
+    env.define("hypot".into(), Value::NativeFn(hypot));
+
+    if rounding::is_rigorous() {
+        // The f64 constants are both just below the real ones
+        env.define("PI".into(), Value::Interval(std::f64::consts::PI, std::f64::consts::PI.next_up()));
+        env.define("E".into(), Value::Interval(std::f64::consts::E, std::f64::consts::E.next_up()));
+    } else {
+        env.define("PI".into(), Value::Number(std::f64::consts::PI));
+        env.define("E".into(), Value::Number(std::f64::consts::E));
+    }
 
     env.define("truncate".into(), Value::NativeFn(truncate));
     env.define("floor".into(), Value::NativeFn(floor));
@@ -42,34 +52,80 @@ pub fn register(env: &mut Environment) {
 }
 
 pub fn sqrt(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+    if let Some(Value::Quantity { value, unit }) = args.first() {
+        let Some(unit) = unit.relative().checked_root(2) else {
+            return Err(eval.error(span, "Unit exponent is too large"));
+        };
+        let root = sqrt(vec![value.as_ref().clone()], span, eval)?;
+        return Ok(Value::Quantity { value: Box::new(root), unit });
+    }
+
     match args.first() {
         Some(Value::Number(n)) => Ok(Value::Number(n.sqrt())),
         Some(Value::Interval(min, max)) => {
             if *min < 0.0 {
                 return Err(eval.error(span, "Cannot take sqrt of negative interval"));
             }
-            Ok(Value::Interval(min.sqrt(), max.sqrt()))
+            Ok(Value::Interval(rounding::sqrt_down(*min), rounding::sqrt_up(*max)))
         }
         _ => Err(eval.error(span, "sqrt() expects 1 number or interval")),
     }
 }
 
 pub fn sin(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
-    match args.first() {
+    match angle(args.first(), "sin", &span, eval)? {
         Some(Value::Number(n)) => Ok(Value::Number(n.sin())),
-        _ => Err(eval.error(span, "sin() expects 1 number")),
+        Some(Value::Interval(min, max)) => Ok(periodic_interval(*min, *max, f64::sin, PI / 2.0, -PI / 2.0)),
+        _ => Err(eval.error(span, "sin() expects 1 number or interval")),
     }
 }
 
 pub fn cos(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
-    match args.first() {
+    match angle(args.first(), "cos", &span, eval)? {
         Some(Value::Number(n)) => Ok(Value::Number(n.cos())),
-        _ => Err(eval.error(span, "cos() expects 1 number")),
+        Some(Value::Interval(min, max)) => Ok(periodic_interval(*min, *max, f64::cos, 0.0, PI)),
+        _ => Err(eval.error(span, "cos() expects 1 number or interval")),
     }
 }
 
+// Trig takes plain numbers, or dimensionless quantities like `90 deg` of `import nonsi`,
+// which are already in radians
+#[allow(clippy::result_large_err)]
+fn angle<'a>(arg: Option<&'a Value>, name: &str, span: &TokenSpan, eval: &mut Evaluator) -> Result<Option<&'a Value>, Error> {
+    match arg {
+        Some(Value::Quantity { value, unit }) if unit.is_dimensionless() => Ok(Some(value.as_ref())),
+        Some(Value::Quantity { unit, .. }) => Err(eval.error(
+            span.clone(),
+            format!("{}() expects a dimensionless value or an angle, got a value in {}", name, unit),
+        )),
+        other => Ok(other),
+    }
+}
+
+// Range of sin/cos over [min..max], peak and trough are where the function reaches 1 and -1
+fn periodic_interval(min: f64, max: f64, f: fn(f64) -> f64, peak: f64, trough: f64) -> Value {
+    let slack = 1e-12 * (1.0 + min.abs().max(max.abs())); // only ever widens the result
+    let reaches = |at: f64| {
+        let k = ((min - at) / (2.0 * PI)).floor();
+        (0..3).any(|i| {
+            let t = at + 2.0 * PI * (k + i as f64);
+            t >= min - slack && t <= max + slack
+        })
+    };
+
+    let (a, b) = (f(min), f(max));
+    let low = if reaches(trough) { -1.0 } else { rounding::down(a.min(b)).max(-1.0) };
+    let high = if reaches(peak) { 1.0 } else { rounding::up(a.max(b)).min(1.0) };
+    Value::Interval(low, high)
+}
+
+// For increasing functions the range is just the image of both bounds
+fn increasing_interval(min: f64, max: f64, f: fn(f64) -> f64) -> Value {
+    Value::Interval(rounding::down(f(min)), rounding::up(f(max)))
+}
+
 pub fn tan(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
-    match args.first() {
+    match angle(args.first(), "tan", &span, eval)? {
         Some(Value::Number(n)) => Ok(Value::Number(n.tan())),
         _ => Err(eval.error(span, "tan() expects 1 number")),
     }
@@ -78,35 +134,68 @@ pub fn tan(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Va
 pub fn log10(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
     match args.first() {
         Some(Value::Number(n)) => Ok(Value::Number(n.log10())),
-        _ => Err(eval.error(span, "log10() expects 1 number")),
+        Some(Value::Interval(min, max)) => {
+            if *min <= 0.0 {
+                return Err(eval.error(span, "Cannot take log10 of an interval with non-positive values"));
+            }
+            Ok(increasing_interval(*min, *max, f64::log10))
+        }
+        _ => Err(eval.error(span, "log10() expects 1 number or interval")),
     }
 }
 
 pub fn log2(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
     match args.first() {
         Some(Value::Number(n)) => Ok(Value::Number(n.log2())),
-        _ => Err(eval.error(span, "log2() expects 1 number")),
+        Some(Value::Interval(min, max)) => {
+            if *min <= 0.0 {
+                return Err(eval.error(span, "Cannot take log2 of an interval with non-positive values"));
+            }
+            Ok(increasing_interval(*min, *max, f64::log2))
+        }
+        _ => Err(eval.error(span, "log2() expects 1 number or interval")),
     }
 }
 
 pub fn ln(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
     match args.first() {
         Some(Value::Number(n)) => Ok(Value::Number(n.ln())),
-        _ => Err(eval.error(span, "ln() expects 1 number")),
+        Some(Value::Interval(min, max)) => {
+            if *min <= 0.0 {
+                return Err(eval.error(span, "Cannot take ln of an interval with non-positive values"));
+            }
+            Ok(increasing_interval(*min, *max, f64::ln))
+        }
+        _ => Err(eval.error(span, "ln() expects 1 number or interval")),
     }
 }
 
 pub fn exp(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
     match args.first() {
         Some(Value::Number(n)) => Ok(Value::Number(n.exp())),
-        _ => Err(eval.error(span, "exp() expects 1 number")),
+        Some(Value::Interval(min, max)) => Ok(increasing_interval(*min, *max, f64::exp)),
+        _ => Err(eval.error(span, "exp() expects 1 number or interval")),
     }
 }
 
 pub fn abs(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+    if let Some(Value::Quantity { value, unit }) = args.first() {
+        let magnitude = abs(vec![value.as_ref().clone()], span, eval)?;
+        return Ok(Value::Quantity { value: Box::new(magnitude), unit: unit.relative() });
+    }
+
     match args.first() {
         Some(Value::Number(n)) => Ok(Value::Number(n.abs())),
-        _ => Err(eval.error(span, "abs() expects 1 number")),
+        Some(Value::Interval(min, max)) => {
+            if *min >= 0.0 {
+                Ok(Value::Interval(*min, *max))
+            } else if *max <= 0.0 {
+                Ok(Value::Interval(-max, -min))
+            } else {
+                Ok(Value::Interval(0.0, min.abs().max(*max)))
+            }
+        }
+        _ => Err(eval.error(span, "abs() expects 1 number or interval")),
     }
 }
 
@@ -115,6 +204,10 @@ pub fn min(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Va
         return Err(eval.error(span, "min() expects at least 2 numbers"));
     }
 
+    if same_unit(&args, "min", &span, eval)? {
+        return Ok(pick(args, |a, b| a < b));
+    }
+
     let mut min_val = std::f64::INFINITY;
     for arg in args {
         match arg {
@@ -134,6 +227,10 @@ pub fn max(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Va
         return Err(eval.error(span, "max() expects at least 2 numbers"));
     }
 
+    if same_unit(&args, "max", &span, eval)? {
+        return Ok(pick(args, |a, b| a > b));
+    }
+
     let mut max_val = std::f64::NEG_INFINITY;
     for arg in args {
         match arg {
@@ -148,6 +245,36 @@ pub fn max(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Va
     Ok(Value::Number(max_val))
 }
 
+// Whether the arguments of min()/max() are quantities, they then all need the same unit
+#[allow(clippy::result_large_err)]
+fn same_unit(args: &[Value], name: &str, span: &TokenSpan, eval: &mut Evaluator) -> Result<bool, Error> {
+    let mut units = args.iter().map(|arg| match arg {
+        Value::Quantity { value, unit } if matches!(value.as_ref(), Value::Number(_)) => Some(unit),
+        _ => None,
+    });
+    let Some(Some(first)) = units.next() else {
+        return Ok(false);
+    };
+    if units.all(|unit| unit == Some(first)) {
+        return Ok(true);
+    }
+    Err(eval.error(span.clone(), format!("{}() expects only numbers, or quantities of the same unit", name)))
+}
+
+// The argument that wins `better` over all others, as it was given so it keeps its display unit
+fn pick(args: Vec<Value>, better: fn(f64, f64) -> bool) -> Value {
+    let number = |value: &Value| match value {
+        Value::Quantity { value, .. } => match value.as_ref() {
+            Value::Number(n) => *n,
+            _ => f64::NAN,
+        },
+        _ => f64::NAN,
+    };
+    args.into_iter()
+        .reduce(|best, arg| if better(number(&arg), number(&best)) { arg } else { best })
+        .unwrap_or(Value::None)
+}
+
 pub fn deg(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
     match args.first() {
         Some(Value::Number(n)) => Ok(Value::Number(n.to_degrees())),
@@ -173,6 +300,24 @@ pub fn atan2(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<
     }
 }
 
+#[allow(clippy::result_large_err)]
+pub fn hypot(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+    if args.len() < 2 {
+        return Err(eval.error(span, "hypot() expects at least 2 numbers"));
+    }
+
+    // the square root of the sum of squares, so units and intervals follow the usual rules
+    let mut sum: Option<Value> = None;
+    for arg in &args {
+        let square = arg.pow(&Value::Number(2.0)).map_err(|e| eval.error(span.clone(), e.message))?;
+        sum = Some(match sum {
+            Some(sum) => sum.add(&square).map_err(|e| eval.error(span.clone(), format!("hypot() {}", e.message)))?,
+            None => square,
+        });
+    }
+    sqrt(sum.into_iter().collect(), span, eval)
+}
+
 pub fn truncate(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
     if args.len() < 2 {
         return Err(eval.error(span, "truncate() expects two numbers"));
@@ -188,6 +333,10 @@ pub fn truncate(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Resu
 }
 
 pub fn floor(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+    if let Some(Value::Quantity { value, unit }) = args.first() && let Value::Number(n) = value.as_ref() {
+        return Ok(in_shown_unit(*n, unit, f64::floor));
+    }
+
     match args.first() {
         Some(Value::Number(n)) => Ok(Value::Number(n.floor())),
         _ =>
@@ -196,6 +345,10 @@ pub fn floor(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<
 }
 
 pub fn round(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+    if let Some(Value::Quantity { value, unit }) = args.first() && let Value::Number(n) = value.as_ref() {
+        return Ok(in_shown_unit(*n, unit, f64::round));
+    }
+
     match args.first() {
         Some(Value::Number(n)) => Ok(Value::Number(n.round())),
         _ =>
@@ -203,10 +356,18 @@ pub fn round(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<
     }
 }
 
+// Rounds in the unit a value is printed in, `round(2.4 km)` is 2 km and not 2400 m
+fn in_shown_unit(n: f64, unit: &Unit, f: fn(f64) -> f64) -> Value {
+    let offset = unit.offset().unwrap_or(0.0);
+    let scale = unit.display().map_or(1.0, |(_, scale)| scale);
+    let rounded = f((n - offset) / scale) * scale + offset;
+    Value::Quantity { value: Box::new(Value::Number(rounded)), unit: unit.clone() }
+}
+
 // Interval Ops
 pub fn width(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
     match args.first() {
-        Some(Value::Interval(min, max)) => Ok(Value::Number(max - min)),
+        Some(Value::Interval(min, max)) => Ok(Value::Number(rounding::sub_up(*max, *min))),
         _ => Err(eval.error(span, "width() expects 1 interval")),
     }
 }
@@ -7,6 +7,7 @@ pub mod units;
 pub mod string;
 pub mod http;
 pub mod json;
+pub mod solve;
 
 use crate::evaluator::env::Environment;
 use std::collections::HashMap;
@@ -23,10 +24,14 @@ pub fn get_library_registry() -> HashMap<String, LibRegisterFn> {
     registry.insert("time".to_string(), crate::libs::time::register);
     
     registry.insert("units".to_string(), crate::libs::units::register);
+    registry.insert("imperial".to_string(), crate::libs::units::register_imperial);
+    registry.insert("nonsi".to_string(), crate::libs::units::register_nonsi);
 
     registry.insert("string".to_string(), crate::libs::string::register);
     registry.insert("http".to_string(), crate::libs::http::register);
     registry.insert("json".to_string(), crate::libs::json::register);
+
+    registry.insert("solve".to_string(), crate::libs::solve::register);
     
     registry
 }
\ No newline at end of file
@@ -0,0 +1,533 @@
+#![allow(clippy::result_large_err)]
+
+use crate::core::value::{Function, Value};
+use crate::core::rounding;
+use crate::evaluator::env::Environment;
+use crate::evaluator::eval::Evaluator;
+use crate::evaluator::symbolic;
+use crate::core::error::Error;
+use crate::parser::ast::{Expr, Stmt};
+use crate::parser::lexer::TokenSpan;
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+
+// The SK Solve library, answers that are guaranteed by interval arithmetic
+
+const MAX_STEPS: usize = 100_000;
+
+pub fn register(env: &mut Environment) {
+    env.define("roots".into(), Value::NativeFn(roots));
+    env.define("minimize".into(), Value::NativeFn(minimize));
+    env.define("maximize".into(), Value::NativeFn(maximize));
+    env.define("integrate".into(), Value::NativeFn(integrate));
+}
+
+// Something that can be evaluated over intervals, an SK function or a symbolic value
+#[derive(Clone)]
+pub enum Objective {
+    Function(Value),
+    Symbolic { expression: Expr, variables: Vec<String> },
+}
+
+impl Objective {
+    pub fn from_value(value: &Value, eval: &Evaluator) -> Option<Objective> {
+        match value {
+            Value::Function(_) | Value::NativeFn(_) => Some(Objective::Function(value.clone())),
+            Value::Symbolic { expression, .. } => {
+                let inputs = symbolic::collect_inputs(&eval.env.borrow(), expression);
+                let mut variables: Vec<String> = inputs.intervals.iter().map(|(name, _)| name.clone()).collect();
+                variables.extend(inputs.unknowns.iter().cloned());
+
+                Some(Objective::Symbolic {
+                    expression: symbolic::inline_formulas(expression, &inputs.formulas),
+                    variables,
+                })
+            }
+            Value::SymbolicFn { params, expression } => Some(Objective::Symbolic {
+                expression: symbolic::inline_body(&eval.env.borrow(), expression, params),
+                variables: params.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Number of inputs, None for native functions
+    pub fn arity(&self) -> Option<usize> {
+        match self {
+            Objective::Function(Value::Function(func)) => Some(func.params.len()),
+            Objective::Function(_) => None,
+            Objective::Symbolic { variables, .. } => Some(variables.len()),
+        }
+    }
+
+    pub fn eval(&self, eval: &mut Evaluator, args: Vec<Value>, span: &TokenSpan) -> Result<Value, Error> {
+        match self {
+            Objective::Function(func) => eval.call_value(func.clone(), args, span.clone()),
+            Objective::Symbolic { expression, variables } => {
+                let bindings = variables.iter().cloned().zip(args).collect();
+                eval.evaluate_with_bindings(expression.clone(), bindings)
+            }
+        }
+    }
+
+    /// Derivative with respect to the input at `index`, only for symbolic values and single expression functions.
+    /// Symbolic values are differentiated in `env`, functions in their closure
+    pub fn derivative(&self, index: usize, env: &Environment) -> Option<Objective> {
+        match self {
+            Objective::Symbolic { expression, variables } => Some(Objective::Symbolic {
+                expression: symbolic::derivative(expression, variables.get(index)?, env)?,
+                variables: variables.clone(),
+            }),
+            Objective::Function(Value::Function(func)) => {
+                let [Stmt::Expression { expression }] = func.body.as_slice() else {
+                    return None;
+                };
+                let param = func.params.get(index)?.name.token_to_string();
+                let derived = symbolic::derivative(expression, &param, &func.closure.borrow())?;
+
+                Some(Objective::Function(Value::Function(Box::new(Function {
+                    body: vec![Stmt::Expression { expression: derived }],
+                    returns: None, // the derivative is in another unit
+                    ..(**func).clone()
+                }))))
+            }
+            Objective::Function(_) => None,
+        }
+    }
+
+    /// Evaluates over an interval and returns the bounds of the result
+    pub fn range(&self, eval: &mut Evaluator, args: Vec<Value>, span: &TokenSpan) -> Result<(f64, f64), Error> {
+        match self.eval(eval, args, span)? {
+            Value::Number(n) => Ok((n, n)),
+            Value::Interval(min, max) => Ok((min, max)),
+            other => Err(eval.error(
+                span.clone(),
+                format!("Expected a number or interval from the function, got '{}'", other),
+            )),
+        }
+    }
+}
+
+fn objective_arg(args: &[Value], name: &str, span: &TokenSpan, eval: &Evaluator) -> Result<Objective, Error> {
+    args.first()
+        .and_then(|value| Objective::from_value(value, eval))
+        .ok_or_else(|| eval.error(span.clone(), format!("{}() expects a function or a symbolic value", name)))
+}
+
+fn positive_arg(args: &[Value], index: usize, default: f64, name: &str, span: &TokenSpan, eval: &Evaluator) -> Result<f64, Error> {
+    match args.get(index) {
+        Some(Value::Number(n)) if *n > 0.0 => Ok(*n),
+        None => Ok(default),
+        _ => Err(eval.error(span.clone(), format!("{}() tolerance must be a positive number", name))),
+    }
+}
+
+// The search only proves anything with outward rounding, so it runs in rigorous mode whatever
+// mode the script is in, the previous mode is back once it returns
+fn rigorously<T>(search: impl FnOnce() -> T) -> T {
+    let previous = rounding::set_rigorous(true);
+    let result = search();
+    rounding::set_rigorous(previous);
+    result
+}
+
+fn point(x: f64) -> Value {
+    Value::Interval(x, x)
+}
+
+// Interval Newton with bisection as a fallback. Every root in the domain ends up in one of the
+// returned intervals, and each of them is checked to really contain a root
+pub fn roots(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+    rigorously(|| find_roots(args, span, eval))
+}
+
+fn find_roots(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+    let f = objective_arg(&args, "roots", &span, eval)?;
+    if let Some(arity) = f.arity() && arity != 1 {
+        return Err(eval.error(span, format!("roots() expects a function of 1 variable, got {}", arity)));
+    }
+
+    let (a, b) = match args.get(1) {
+        Some(Value::Interval(min, max)) => (*min, *max),
+        _ => return Err(eval.error(span, "roots() expects an interval as the domain")),
+    };
+    let tol = positive_arg(&args, 2, 1e-9, "roots", &span, eval)?;
+    let df = f.derivative(0, &eval.env.borrow());
+
+    let mut work = vec![(a, b, false)];
+    let mut found: Vec<(f64, f64, bool)> = Vec::new();
+    let mut steps = 0;
+
+    while let Some((lo, hi, verified)) = work.pop() {
+        steps += 1;
+        if steps > MAX_STEPS {
+            return Err(eval.error(span, "roots() did not converge, try a larger tolerance"));
+        }
+
+        if verified && hi - lo <= tol {
+            found.push((lo, hi, verified));
+            continue;
+        }
+
+        let (f_lo, f_hi) = f.range(eval, vec![Value::Interval(lo, hi)], &span)?;
+        if f_lo > 0.0 || f_hi < 0.0 {
+            continue; // no root in here
+        }
+
+        if hi - lo <= tol {
+            found.push((lo, hi, verified));
+            continue;
+        }
+
+        if let Some(df) = &df {
+            let (d_lo, d_hi) = df.range(eval, vec![Value::Interval(lo, hi)], &span)?;
+            if d_lo > 0.0 || d_hi < 0.0 {
+                // N(X) = m - f(m) / f'(X), every root in X is also in N(X)
+                let m = lo + (hi - lo) / 2.0;
+                let (fm_lo, fm_hi) = f.range(eval, vec![point(m)], &span)?;
+                let quotients = [(fm_lo, d_lo), (fm_lo, d_hi), (fm_hi, d_lo), (fm_hi, d_hi)];
+                let q_lo = quotients.iter().map(|(x, y)| rounding::div_down(*x, *y)).fold(f64::INFINITY, f64::min);
+                let q_hi = quotients.iter().map(|(x, y)| rounding::div_up(*x, *y)).fold(f64::NEG_INFINITY, f64::max);
+                let (n_lo, n_hi) = (rounding::sub_down(m, q_hi), rounding::sub_up(m, q_lo));
+
+                let (next_lo, next_hi) = (lo.max(n_lo), hi.min(n_hi));
+                if next_lo > next_hi {
+                    continue;
+                }
+
+                // N(X) strictly inside X proves there is exactly one root
+                let contracted = n_lo > lo && n_hi < hi;
+                if next_hi - next_lo < 0.5 * (hi - lo) {
+                    work.push((next_lo, next_hi, verified || contracted));
+                    continue;
+                }
+                if contracted && next_hi - next_lo <= tol {
+                    found.push((next_lo, next_hi, true));
+                    continue;
+                }
+            }
+        }
+
+        let mid = lo + (hi - lo) / 2.0;
+        work.push((mid, hi, false));
+        work.push((lo, mid, false));
+    }
+
+    // Neighbouring boxes around the same root are joined back together
+    found.sort_by(|x, y| x.0.total_cmp(&y.0));
+    let mut merged: Vec<(f64, f64, bool)> = Vec::new();
+    for (lo, hi, verified) in found {
+        match merged.last_mut() {
+            Some(last) if lo <= last.1 => {
+                last.1 = last.1.max(hi);
+                last.2 = last.2 || verified;
+            }
+            _ => merged.push((lo, hi, verified)),
+        }
+    }
+
+    let mut result = Vec::new();
+    for (lo, hi, verified) in merged {
+        if !verified && !sign_change(&f, eval, lo, hi, &span)? {
+            eval.warn(
+                span.clone(),
+                format!("roots() could not verify the root in [{}..{}], it may be a multiple root", lo, hi),
+            );
+        }
+        result.push(Value::Interval(lo, hi));
+    }
+
+    Ok(Value::Array(result))
+}
+
+// A continuous function that is certainly positive at one end and negative at the other has a root between them
+fn sign_change(f: &Objective, eval: &mut Evaluator, lo: f64, hi: f64, span: &TokenSpan) -> Result<bool, Error> {
+    let (l_lo, l_hi) = f.range(eval, vec![point(lo)], span)?;
+    let (h_lo, h_hi) = f.range(eval, vec![point(hi)], span)?;
+    let exact_zero = (l_lo == 0.0 && l_hi == 0.0) || (h_lo == 0.0 && h_hi == 0.0);
+    Ok((l_hi < 0.0 && h_lo > 0.0) || (l_lo > 0.0 && h_hi < 0.0) || exact_zero)
+}
+
+// Domains for each input: one interval, an array of intervals, or for symbolic values the
+// intervals their variables currently hold
+fn domains_arg(f: &Objective, arg: Option<&Value>, name: &str, span: &TokenSpan, eval: &Evaluator) -> Result<Vec<(f64, f64)>, Error> {
+    let values = match (arg, f) {
+        (Some(Value::Interval(..)), _) => vec![arg.cloned().unwrap_or(Value::None)],
+        (Some(Value::Array(items)), _) => items.clone(),
+        (None, Objective::Symbolic { variables, .. }) => variables
+            .iter()
+            .map(|v| eval.env.borrow().get(v).unwrap_or(Value::Unknown))
+            .collect(),
+        _ => return Err(eval.error(span.clone(), format!("{}() expects an interval or an array of intervals as domains", name))),
+    };
+
+    let mut domains = Vec::new();
+    for value in values {
+        match value {
+            Value::Interval(min, max) => domains.push((min, max)),
+            other => {
+                return Err(eval.error(span.clone(), format!("{}() domains must be intervals, got '{}'", name, other)));
+            }
+        }
+    }
+
+    if let Some(arity) = f.arity() && arity != domains.len() {
+        return Err(eval.error(
+            span.clone(),
+            format!("{}() got {} domains for a function of {} variables", name, domains.len(), arity),
+        ));
+    }
+    Ok(domains)
+}
+
+fn box_values(current: &[(f64, f64)]) -> Vec<Value> {
+    current.iter().map(|(lo, hi)| Value::Interval(*lo, *hi)).collect()
+}
+
+pub fn minimize(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+    rigorously(|| optimize(args, span, eval, "minimize", false))
+}
+
+pub fn maximize(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+    rigorously(|| optimize(args, span, eval, "maximize", true))
+}
+
+// Work waiting to be split, the heap pops the one with the lowest key first
+struct Candidate<T> {
+    key: f64,
+    item: T,
+}
+
+impl<T> Ord for Candidate<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.key.total_cmp(&self.key)
+    }
+}
+
+impl<T> PartialOrd for Candidate<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> PartialEq for Candidate<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Candidate<T> {}
+
+fn widest(current: &[(f64, f64)]) -> f64 {
+    current.iter().map(|(lo, hi)| hi - lo).fold(0.0, f64::max)
+}
+
+// Halves a box along its widest side, None once it can't be split any further
+fn split(current: &[(f64, f64)]) -> Option<[Vec<(f64, f64)>; 2]> {
+    let (axis, (lo, hi)) = current
+        .iter()
+        .copied()
+        .enumerate()
+        .max_by(|a, b| (a.1.1 - a.1.0).total_cmp(&(b.1.1 - b.1.0)))?;
+    let mid = lo + (hi - lo) / 2.0;
+    if mid <= lo || mid >= hi {
+        return None;
+    }
+
+    let (mut left, mut right) = (current.to_vec(), current.to_vec());
+    left[axis] = (lo, mid);
+    right[axis] = (mid, hi);
+    Some([left, right])
+}
+
+// Branch and bound: the box with the lowest possible value is split until that value is within
+// the tolerance of the best value actually found, boxes that can't beat it are dropped.
+// Returns [optimum, box], the optimum surely contains the real one and the box every point reaching it
+fn optimize(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator, name: &str, maximize: bool) -> Result<Value, Error> {
+    let f = objective_arg(&args, name, &span, eval)?;
+    let domains = domains_arg(&f, args.get(1), name, &span, eval)?;
+    let tol = positive_arg(&args, 2, 1e-6, name, &span, eval)?;
+
+    // maximizing f is minimizing -f
+    let range = |current: &[(f64, f64)], eval: &mut Evaluator| -> Result<(f64, f64), Error> {
+        let (lo, hi) = f.range(eval, box_values(current), &span)?;
+        Ok(if maximize { (-hi, -lo) } else { (lo, hi) })
+    };
+    let midpoint = |current: &[(f64, f64)]| -> Vec<(f64, f64)> {
+        current.iter().map(|(lo, hi)| { let m = lo + (hi - lo) / 2.0; (m, m) }).collect()
+    };
+
+    let (first_low, _) = range(&domains, eval)?;
+    let mut best = range(&midpoint(&domains), eval)?.1;
+    let mut work = BinaryHeap::from([Candidate { key: first_low, item: domains }]);
+    let mut steps = 0;
+
+    // the lowest box is split until the optimum is known to the tolerance
+    while let Some(lowest) = work.peek() {
+        if best - lowest.key <= tol {
+            break;
+        }
+        let Some(halves) = split(&lowest.item) else { break };
+
+        steps += 1;
+        if steps > MAX_STEPS {
+            return Err(eval.error(span, format!("{}() did not converge, try a larger tolerance", name)));
+        }
+
+        work.pop();
+        for child in halves {
+            let (low, _) = range(&child, eval)?;
+            best = best.min(range(&midpoint(&child), eval)?.1);
+            if low <= best {
+                work.push(Candidate { key: low, item: child });
+            }
+        }
+    }
+
+    // then the boxes left are split widest first for a while, so the box around the points reaching it narrows
+    let arg_tol = tol.sqrt();
+    let mut queue: VecDeque<_> = work.into_iter().map(|c| (c.key, c.item)).collect();
+    let mut work = Vec::new();
+    let mut splits = 0;
+    while let Some((low, current)) = queue.pop_front() {
+        if low > best {
+            continue;
+        }
+        let halves = match split(&current) {
+            Some(halves) if splits < MAX_STEPS / 10 && widest(&current) > arg_tol => halves,
+            _ => {
+                work.push((low, current));
+                continue;
+            }
+        };
+
+        splits += 1;
+        for child in halves {
+            let (child_low, _) = range(&child, eval)?;
+            best = best.min(range(&midpoint(&child), eval)?.1);
+            queue.push_back((child_low, child));
+        }
+    }
+    work.retain(|(low, _)| *low <= best);
+
+    let lowest = work.iter().map(|(low, _)| *low).fold(best, f64::min);
+    let optimum = if maximize { Value::Interval(-best, -lowest) } else { Value::Interval(lowest, best) };
+
+    let mut hull: Vec<(f64, f64)> = Vec::new();
+    for (_, current) in &work {
+        if hull.is_empty() {
+            hull = current.clone();
+        }
+        for (h, c) in hull.iter_mut().zip(current) {
+            *h = (h.0.min(c.0), h.1.max(c.1));
+        }
+    }
+
+    let arg = match box_values(&hull).as_slice() {
+        [single] => single.clone(),
+        values => Value::Array(values.to_vec()),
+    };
+    Ok(Value::Array(vec![optimum, arg]))
+}
+
+// Interval bounds helpers, rounded outward in rigorous mode
+type Bounds = (f64, f64);
+
+fn bounds_add(x: Bounds, y: Bounds) -> Bounds {
+    (rounding::add_down(x.0, y.0), rounding::add_up(x.1, y.1))
+}
+
+fn bounds_sub(x: Bounds, y: Bounds) -> Bounds {
+    (rounding::sub_down(x.0, y.1), rounding::sub_up(x.1, y.0))
+}
+
+fn bounds_mul(x: Bounds, y: Bounds) -> Bounds {
+    let products = [(x.0, y.0), (x.0, y.1), (x.1, y.0), (x.1, y.1)];
+    (
+        products.iter().map(|(a, b)| rounding::mul_down(*a, *b)).fold(f64::INFINITY, f64::min),
+        products.iter().map(|(a, b)| rounding::mul_up(*a, *b)).fold(f64::NEG_INFINITY, f64::max),
+    )
+}
+
+fn bounds_scale(x: Bounds, divisor: f64) -> Bounds {
+    (rounding::div_down(x.0, divisor), rounding::div_up(x.1, divisor))
+}
+
+// Encloses the integral of f over [lo..hi]. The Riemann bound f([lo..hi]) * width always works,
+// when f'' is known the Taylor expansion around the midpoint m is used too:
+// f(m) * w + f'(m) * ((hi - m)^2 - (lo - m)^2) / 2 + f''([lo..hi]) * ((hi - m)^3 + (m - lo)^3) / 6
+fn integral_piece(f: &Objective, derivatives: Option<&(Objective, Objective)>, eval: &mut Evaluator, lo: f64, hi: f64, span: &TokenSpan) -> Result<Bounds, Error> {
+    let width = bounds_sub((hi, hi), (lo, lo));
+    let riemann = bounds_mul(f.range(eval, vec![Value::Interval(lo, hi)], span)?, width);
+
+    let Some((df, ddf)) = derivatives else { return Ok(riemann) };
+    let m = lo + (hi - lo) / 2.0;
+    let (right, left) = (bounds_sub((hi, hi), (m, m)), bounds_sub((m, m), (lo, lo)));
+
+    let linear = bounds_scale(bounds_sub(bounds_mul(right, right), bounds_mul(left, left)), 2.0);
+    let cubic = bounds_scale(
+        bounds_add(bounds_mul(bounds_mul(right, right), right), bounds_mul(bounds_mul(left, left), left)),
+        6.0,
+    );
+
+    let taylor = bounds_add(
+        bounds_add(
+            bounds_mul(f.range(eval, vec![point(m)], span)?, width),
+            bounds_mul(df.range(eval, vec![point(m)], span)?, linear),
+        ),
+        bounds_mul(ddf.range(eval, vec![Value::Interval(lo, hi)], span)?, cubic),
+    );
+
+    // both contain the integral, so does their intersection
+    let (low, high) = (riemann.0.max(taylor.0), riemann.1.min(taylor.1));
+    Ok(if low <= high { (low, high) } else { riemann })
+}
+
+// Adaptive subdivision: the piece whose enclosure is widest is split until the total width is
+// below the tolerance, the result always contains the exact integral
+pub fn integrate(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+    rigorously(|| enclose_integral(args, span, eval))
+}
+
+fn enclose_integral(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+    let f = objective_arg(&args, "integrate", &span, eval)?;
+    let domains = domains_arg(&f, args.get(1), "integrate", &span, eval)?;
+    let [(a, b)] = domains.as_slice() else {
+        return Err(eval.error(span, "integrate() expects a function of 1 variable"));
+    };
+    let tol = positive_arg(&args, 2, 1e-6, "integrate", &span, eval)?;
+    let derivatives = {
+        let env = eval.env.borrow();
+        f.derivative(0, &env).and_then(|df| {
+            let ddf = df.derivative(0, &env)?;
+            Some((df, ddf))
+        })
+    };
+
+    let first = integral_piece(&f, derivatives.as_ref(), eval, *a, *b, &span)?;
+    let mut total_width = first.1 - first.0;
+    let mut work = BinaryHeap::from([Candidate { key: -total_width, item: (*a, *b, first) }]);
+    let mut steps = 0;
+
+    while total_width > tol && let Some(widest) = work.peek() {
+        let (lo, hi, _) = widest.item;
+        let mid = lo + (hi - lo) / 2.0;
+        steps += 1;
+        if steps > MAX_STEPS || mid <= lo || mid >= hi {
+            eval.warn(span.clone(), "integrate() stopped before reaching the tolerance, the result is wider");
+            break;
+        }
+
+        let Some(Candidate { item: (_, _, enclosure), .. }) = work.pop() else { break };
+        total_width -= enclosure.1 - enclosure.0;
+        for (piece_lo, piece_hi) in [(lo, mid), (mid, hi)] {
+            let piece = integral_piece(&f, derivatives.as_ref(), eval, piece_lo, piece_hi, &span)?;
+            total_width += piece.1 - piece.0;
+            work.push(Candidate { key: -(piece.1 - piece.0), item: (piece_lo, piece_hi, piece) });
+        }
+    }
+
+    let (low, high) = work.into_iter().fold((0.0, 0.0), |total, piece| bounds_add(total, piece.item.2));
+    Ok(Value::Interval(low, high))
+}
@@ -2,8 +2,12 @@ use crate::core::units::Unit;
 use crate::core::value::Value;
 use crate::evaluator::env::Environment;
 use crate::evaluator::eval::Evaluator;
-use crate::parser::lexer::TokenSpan;
-use crate::core::error::Error;
+use crate::parser::ast::{Expr, UnitExpr};
+use crate::parser::lexer::{self, TokenSpan};
+use crate::parser::parser::Parser;
+use crate::core::error::{Error, ErrorReporter};
+use std::cell::RefCell;
+use std::rc::Rc;
 
 fn unit_value(symbol: &str) -> Value {
 	Value::Quantity {
@@ -26,6 +30,43 @@ fn scaled_unit_value(symbol: &str, scale: f64) -> Value {
 	}
 }
 
+// One step of a scale whose zero sits `offset` base units above the base unit's zero
+fn offset_unit_value(symbol: &str, scale: f64, offset: f64) -> Value {
+	Value::Quantity {
+		value: Box::new(Value::Number(scale + offset)),
+		unit: Unit::base(symbol).with_offset(offset),
+	}
+}
+
+// A difference between two values of an offset scale, `deltaC` is one `K` of difference
+fn difference_unit_value(symbol: &str, scale: f64) -> Value {
+	Value::Quantity {
+		value: Box::new(Value::Number(scale)),
+		unit: Unit::base(symbol).as_difference(),
+	}
+}
+
+// SI prefixes, any unit of the library can take one, `kN`, `MPa`, `µs`, see `Evaluator::library_unit`
+const PREFIXES: [(&str, f64); 21] = [
+	("y", 1e-24), ("z", 1e-21), ("a", 1e-18), ("f", 1e-15), ("p", 1e-12), ("n", 1e-9),
+	("u", 1e-6), ("µ", 1e-6), ("m", 1e-3), ("c", 1e-2), ("d", 1e-1),
+	("da", 1e1), ("h", 1e2), ("k", 1e3), ("M", 1e6), ("G", 1e9), ("T", 1e12),
+	("P", 1e15), ("E", 1e18), ("Z", 1e21), ("Y", 1e24),
+];
+
+// Decimals `units.format()` writes at most, a double has no more than 17 significant digits
+const MAX_PRECISION: usize = 17;
+
+/// Libraries whose names can be written as units, the units library and its opt-in packs
+pub const LIBRARIES: [&str; 3] = ["units", "imperial", "nonsi"];
+
+/// Every way to read `name` as a prefix and a unit name, `"mA"` gives `(1e-3, "A")`
+pub fn prefixed(name: &str) -> impl Iterator<Item = (f64, &str)> {
+	PREFIXES.iter().filter_map(move |(prefix, factor)| {
+		name.strip_prefix(prefix).filter(|rest| !rest.is_empty()).map(|rest| (*factor, rest))
+	})
+}
+
 pub fn register(env: &mut Environment) {
 	// SI base units
 	env.define("m".into(), unit_value("m"));
@@ -71,22 +112,92 @@ pub fn register(env: &mut Environment) {
 	env.define("g".into(), scaled_unit_value("kg", 0.001));
 	env.define("mg".into(), scaled_unit_value("kg", 0.000001));
 
-	// Temperature scales
-	env.define("dC".into(), Value::Quantity {
-		value: Box::new(Value::Number(1.0)),
-		unit: Unit::base("dC"),
-	});
-	env.define("dK".into(), Value::Quantity {
-		value: Box::new(Value::Number(1.0)),
-		unit: Unit::base("dK"),
-	});
+	// Temperature scales, values in degC and degF are absolute, see `Unit::with_offset`
+	let celsius = offset_unit_value("K", 1.0, 273.15);
+	let fahrenheit = offset_unit_value("K", 5.0 / 9.0, 273.15 - 32.0 * 5.0 / 9.0);
+	env.define("degC".into(), celsius.clone());
+	env.define("degF".into(), fahrenheit);
+
+	// Older names, kept so `dC` and `K` convert into each other
+	env.define("dC".into(), celsius);
+	env.define("dK".into(), unit_value("K"));
+
+	// Temperature differences
+	env.define("deltaC".into(), difference_unit_value("K", 1.0));
+	env.define("deltaF".into(), difference_unit_value("K", 5.0 / 9.0));
 
 	env.define("define".into(), Value::NativeFn(define));
+	env.define("dimension".into(), Value::NativeFn(dimension));
+	env.define("use".into(), Value::NativeFn(use_system));
+	env.define("parse".into(), Value::NativeFn(parse));
+	env.define("format".into(), Value::NativeFn(format));
+}
+
+// Scaled copies of a unit made of several dimensions, `psi` is 6894.757 `Pa`
+fn scaled_from(unit: &Unit, scale: f64) -> Value {
+	Value::Quantity {
+		value: Box::new(Value::Number(scale)),
+		unit: unit.clone(),
+	}
+}
+
+/// `import imperial`, US customary and imperial units in SI terms
+pub fn register_imperial(env: &mut Environment) {
+	let m = Unit::base("m");
+	let velocity = m.div(&Unit::base("s"));
+	let newton = Unit::base("kg").mul(&m).div(&Unit::base("s").pow(2));
+
+	// Length, `in` is a keyword so inches are `inch`
+	env.define("inch".into(), scaled_unit_value("m", 0.0254));
+	env.define("ft".into(), scaled_unit_value("m", 0.3048));
+	env.define("yd".into(), scaled_unit_value("m", 0.9144));
+	env.define("mi".into(), scaled_unit_value("m", 1609.344));
+	env.define("nmi".into(), scaled_unit_value("m", 1852.0));
+	env.define("acre".into(), scaled_from(&m.pow(2), 4046.8564224));
+
+	// Mass and force
+	env.define("lb".into(), scaled_unit_value("kg", 0.45359237));
+	env.define("oz".into(), scaled_unit_value("kg", 0.028349523125));
+	env.define("ton".into(), scaled_unit_value("kg", 907.18474));
+	env.define("lbf".into(), scaled_from(&newton, 4.4482216152605));
+
+	// Volume, in US gallons
+	env.define("gal".into(), scaled_unit_value("L", 3.785411784));
+	env.define("qt".into(), scaled_unit_value("L", 0.946352946));
+	env.define("pt".into(), scaled_unit_value("L", 0.473176473));
+	env.define("floz".into(), scaled_unit_value("L", 0.0295735295625));
+
+	// Pressure, energy and power
+	env.define("psi".into(), scaled_from(&newton.div(&m.pow(2)), 6894.757293168));
+	env.define("BTU".into(), scaled_from(&newton.mul(&m), 1055.05585262));
+	env.define("hp".into(), scaled_from(&newton.mul(&m).div(&Unit::base("s")), 745.69987158227));
+
+	// Speed
+	env.define("mph".into(), scaled_from(&velocity, 0.44704));
+	env.define("kn".into(), scaled_from(&velocity, 1852.0 / 3600.0));
+}
+
+/// `import nonsi`, angles, longer times and data sizes
+pub fn register_nonsi(env: &mut Environment) {
+	// Angles are dimensionless, a radian is 1
+	env.define("rad".into(), unit_from(Unit::dimensionless()));
+	env.define("deg".into(), scaled_from(&Unit::dimensionless(), std::f64::consts::PI / 180.0));
+
+	env.define("day".into(), scaled_unit_value("s", 86400.0));
+	env.define("week".into(), scaled_unit_value("s", 604800.0));
+
+	// Data, `kB` and `MB` come from the SI prefixes, the binary sizes are written out
+	env.define("B".into(), unit_value("B"));
+	env.define("bit".into(), scaled_unit_value("B", 0.125));
+	env.define("KiB".into(), scaled_unit_value("B", 1024.0));
+	env.define("MiB".into(), scaled_unit_value("B", 1024.0 * 1024.0));
+	env.define("GiB".into(), scaled_unit_value("B", 1024.0 * 1024.0 * 1024.0));
+	env.define("TiB".into(), scaled_unit_value("B", 1024.0 * 1024.0 * 1024.0 * 1024.0));
 }
 
 fn define(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
-	if args.len() != 2 {
-		return Err(eval.error(span, "define() expects name and value"));
+	if args.len() != 2 && args.len() != 3 {
+		return Err(eval.error(span, "define() expects name, value and an optional offset"));
 	}
 
 	let name = match &args[0] {
@@ -94,15 +205,234 @@ fn define(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Val
 		_ => return Err(eval.error(span, "define() name must be a string")),
 	};
 
-	let quantity = match &args[1] {
+	let mut quantity = match &args[1] {
 		Value::Quantity { .. } => args[1].clone(),
 		_ => return Err(eval.error(span, "define() value must be a unit quantity")),
 	};
 
-	eval.env.borrow_mut().define(name.clone(), quantity.clone());
-	if let Ok(Value::Module(units_mod)) = eval.env.borrow().get("units") {
-		units_mod.borrow_mut().define(name, quantity);
+	// `units.define("degRe", 1.25 K, 273.15 K)`, the zero of the new scale in the same dimension
+	if let Some(offset) = args.get(2) {
+		let Value::Quantity { value, unit } = quantity else {
+			return Err(eval.error(span, "define() value must be a unit quantity"));
+		};
+		let zero = match offset {
+			Value::Quantity { value: zero, unit: zero_unit } if *zero_unit == unit => zero.as_ref(),
+			Value::Number(_) => offset,
+			_ => return Err(eval.error(span, "define() offset must be a quantity of the same unit")),
+		};
+		let (Value::Number(size), Value::Number(zero)) = (value.as_ref(), zero) else {
+			return Err(eval.error(span, "define() value and offset of an offset unit must be exact numbers"));
+		};
+		quantity = Value::Quantity {
+			value: Box::new(Value::Number(size + zero)),
+			unit: unit.relative().with_offset(*zero),
+		};
 	}
 
+	add_unit(eval, name, quantity);
 	Ok(Value::None)
 }
+
+// `units.dimension("USD")`, a new base dimension with a unit of the same name
+#[allow(clippy::result_large_err)]
+fn dimension(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+	let name = match args.first() {
+		Some(Value::String(name)) if args.len() == 1 => name.clone(),
+		_ => return Err(eval.error(span, "dimension() expects a name, like \"USD\"")),
+	};
+	if name.is_empty() || !name.chars().all(|c| c.is_alphabetic() || c == '_') {
+		return Err(eval.error(span, format!("dimension() name must be made of letters, got '{}'", name)));
+	}
+	if library_units(eval).contains(&name) {
+		return Err(eval.error(span, format!("'{}' is already a unit", name)));
+	}
+
+	let unit = unit_value(&name);
+	add_unit(eval, name, unit.clone());
+	Ok(unit)
+}
+
+// `units.use(shop)`, the units a script declared, after `import "shop.sk"`. Units are merged, a
+// system can be used with others as long as the units they share are the same
+#[allow(clippy::result_large_err)]
+fn use_system(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+	let system = match args.first() {
+		Some(Value::Module(module)) if args.len() == 1 => module.clone(),
+		_ => return Err(eval.error(span, "use() expects an imported unit system, like `import \"shop.sk\"`")),
+	};
+	let Some(table) = units_module(&system.borrow()) else {
+		return Err(eval.error(span, "use() expects a unit system, a script that imports units"));
+	};
+
+	let table = table.borrow();
+	for name in table.names() {
+		let Ok(unit @ Value::Quantity { .. }) = table.get(&name) else {
+			continue;
+		};
+		match library_unit(eval, &name) {
+			Some(known) if !same_unit(&known, &unit) => {
+				return Err(eval.error(span, format!("The unit system's '{}' is {}, but '{}' is already {}", name, unit, name, known)));
+			}
+			Some(_) => {}
+			None => add_unit(eval, name, unit),
+		}
+	}
+	Ok(Value::None)
+}
+
+// Units made by a script live in its units module, whatever name it was imported as, where they
+// can take prefixes and be parsed
+fn add_unit(eval: &mut Evaluator, name: String, unit: Value) {
+	let table = units_module(&eval.env.borrow());
+	match table {
+		Some(table) => table.borrow_mut().define(name, unit),
+		None => eval.env.borrow_mut().define(name, unit),
+	}
+}
+
+fn units_module(env: &Environment) -> Option<Rc<RefCell<Environment>>> {
+	env.unit_libraries().into_iter().find(|(library, _)| library == "units").map(|(_, module)| module)
+}
+
+fn library_unit(eval: &Evaluator, name: &str) -> Option<Value> {
+	let libraries = eval.env.borrow().unit_libraries();
+	libraries.iter().find_map(|(_, module)| module.borrow().get(name).ok())
+}
+
+// Both the dimension and the size, and the zero of offset units
+fn same_unit(a: &Value, b: &Value) -> bool {
+	match (a, b) {
+		(Value::Quantity { value: a_size, unit: a_unit }, Value::Quantity { value: b_size, unit: b_unit }) => {
+			a_size == b_size && a_unit == b_unit && a_unit.offset() == b_unit.offset()
+		}
+		_ => false,
+	}
+}
+
+#[allow(clippy::result_large_err)]
+fn parse(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+	let text = match args.first() {
+		Some(Value::String(text)) if args.len() == 1 => text.clone(),
+		_ => return Err(eval.error(span, "parse() expects a string, like \"12.5 km/h\"")),
+	};
+
+	let expr = read_quantity(&text, &span, eval)?;
+	eval.evaluate_expression(expr)
+}
+
+#[allow(clippy::result_large_err)]
+fn format(args: Vec<Value>, span: TokenSpan, eval: &mut Evaluator) -> Result<Value, Error> {
+	if args.len() != 2 && args.len() != 3 {
+		return Err(eval.error(span, "format() expects a quantity, a unit and an optional precision"));
+	}
+
+	// the unit as text, "km/h", or as a unit value, `km`
+	let (target, name) = match &args[1] {
+		Value::String(text) => {
+			let expr = read_quantity(&format!("1 {}", text), &span, eval)?;
+			(eval.evaluate_expression(expr)?, text.trim().to_string())
+		}
+		Value::Quantity { value, unit } => match (value.as_ref(), unit.display()) {
+			(Value::Number(n), Some((name, scale))) if *n == scale + unit.offset().unwrap_or(0.0) => {
+				(args[1].clone(), name.to_string())
+			}
+			_ => return Err(eval.error(span, "format() needs a unit name, give it as a string like \"km/h\"")),
+		},
+		other => return Err(eval.error(span, format!("format() expects a unit, got '{}'", other))),
+	};
+
+	let converted = args[0].in_unit(&target).map_err(|e| eval.error(span.clone(), e.message))?;
+	let shown = match args.get(2) {
+		None => converted.to_string(),
+		Some(Value::Number(p)) if (0.0..=MAX_PRECISION as f64).contains(p) && p.fract() == 0.0 => {
+			let p = *p as usize;
+			match converted {
+				Value::Number(n) => format!("{:.*}", p, n),
+				Value::Interval(min, max) => format!("[{:.*}..{:.*}]", p, min, p, max),
+				other => other.to_string(),
+			}
+		}
+		Some(other) => {
+			return Err(eval.error(span, format!("format() precision must be a whole number up to {}, got '{}'", MAX_PRECISION, other)));
+		}
+	};
+
+	Ok(Value::String(format!("{} {}", shown, name)))
+}
+
+// Parses text like "[3..4] kg" on its own, so its errors name the text and not the script
+#[allow(clippy::result_large_err)]
+fn read_quantity(text: &str, span: &TokenSpan, eval: &mut Evaluator) -> Result<Expr, Error> {
+	let reporter = Rc::new(RefCell::new(ErrorReporter::new()));
+	let unreadable = |e: Error| format!("Cannot read '{}' as a quantity, {}", text, e.message);
+	let tokens = lexer::tokenize(text.to_string(), reporter.clone()).map_err(|e| eval.error(span.clone(), unreadable(e)))?;
+	let expr = Parser::new(tokens, reporter).quantity().map_err(|e| eval.error(span.clone(), unreadable(e)))?;
+
+	if let Expr::Quantity { unit, .. } = &expr {
+		let known = library_units(eval);
+		let is_known = |name: &str| known.iter().any(|k| k == name) || prefixed(name).any(|(_, rest)| known.iter().any(|k| k == rest));
+		for name in unit_names(unit) {
+			if !is_known(&name) {
+				return Err(eval.error(span.clone(), unknown_unit(&name, text, &known)));
+			}
+		}
+	}
+	Ok(expr)
+}
+
+// Every unit name of the imported unit libraries
+fn library_units(eval: &Evaluator) -> Vec<String> {
+	let libraries = eval.env.borrow().unit_libraries();
+	libraries
+		.into_iter()
+		.flat_map(|(_, module)| {
+			let module = module.borrow();
+			module.names().into_iter().filter(|name| matches!(module.get(name), Ok(Value::Quantity { .. }))).collect::<Vec<_>>()
+		})
+		.collect()
+}
+
+fn unit_names(unit: &UnitExpr) -> Vec<String> {
+	match unit {
+		UnitExpr::Name(name) => vec![name.token_to_string()],
+		UnitExpr::Mul(left, right) | UnitExpr::Div(left, right) => {
+			let mut names = unit_names(left);
+			names.extend(unit_names(right));
+			names
+		}
+		UnitExpr::Pow(base, _) => unit_names(base),
+	}
+}
+
+// "Unknown unit 'kmh' in '12 kmh', did you mean km, kn?"
+fn unknown_unit(name: &str, text: &str, known: &[String]) -> String {
+	let mut close: Vec<(usize, &String)> = known
+		.iter()
+		.map(|candidate| (edit_distance(name, candidate), candidate))
+		.filter(|(distance, _)| *distance <= 2)
+		.collect();
+	close.sort();
+	close.dedup();
+
+	let message = format!("Unknown unit '{}' in '{}'", name, text);
+	if close.is_empty() {
+		return message;
+	}
+	let names: Vec<&str> = close.iter().take(4).map(|(_, name)| name.as_str()).collect();
+	format!("{}, did you mean {}?", message, names.join(", "))
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+	let b: Vec<char> = b.chars().collect();
+	let mut row: Vec<usize> = (0..=b.len()).collect();
+	for (i, ca) in a.chars().enumerate() {
+		let mut previous = row[0];
+		row[0] = i + 1;
+		for (j, cb) in b.iter().enumerate() {
+			let replaced = previous + usize::from(ca != *cb);
+			previous = row[j + 1];
+			row[j + 1] = replaced.min(previous + 1).min(row[j] + 1);
+		}
+	}
+	row[b.len()]
+}
@@ -11,8 +11,8 @@ use sk_lang::core::value::Value;
 
 mod repl;
 
-fn run(path: &Path, safe_mode: bool) {
-    let mut interpreter = SKInterpreter::new_with_options(safe_mode);
+fn run(path: &Path, safe_mode: bool, rigorous: bool) {
+    let mut interpreter = SKInterpreter::new_with_options(safe_mode).rigorous(rigorous);
 
     match interpreter.execute(&path) {
         Ok(value) => {
@@ -30,6 +30,26 @@ fn run(path: &Path, safe_mode: bool) {
     }
 }
 
+// --check: reports dimension mismatches without running the script
+fn check_dimensions(path: &Path) {
+    let mut interpreter = SKInterpreter::new();
+
+    match interpreter.check_dimensions(path) {
+        Ok(errors) if errors.is_empty() => println!("{}: no dimension errors found", path.display()),
+        Ok(errors) => {
+            for error in &errors {
+                eprintln!("{}", error);
+            }
+            eprintln!("{} dimension error{} found", errors.len(), if errors.len() == 1 { "" } else { "s" });
+            process::exit(1)
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1)
+        }
+    }
+}
+
 fn check(path: &Path) -> Result<(), String> {
     if !path.exists() {
         return Err(format!("Couldn't find file: '{}'", path.display()));
@@ -61,13 +81,15 @@ fn create_project(name: String) {
 fn main() {
     let raw_args: Vec<String> = env::args().skip(1).collect();
     let safe = raw_args.iter().any(|arg| arg == "--safe");
+    let rigorous = raw_args.iter().any(|arg| arg == "--rigorous");
+    let dimensions = raw_args.iter().any(|arg| arg == "--check");
     let args: Vec<String> = raw_args
         .into_iter()
-        .filter(|arg| arg != "--safe")
+        .filter(|arg| arg != "--safe" && arg != "--rigorous" && arg != "--check")
         .collect();
 
     if args.is_empty() {
-        repl::run_repl(safe, NAME, VERSION);
+        repl::run_repl(safe, rigorous, NAME, VERSION);
         return;
     }
 
@@ -110,7 +132,12 @@ fn main() {
         process::exit(1)
     }
 
-    run(&path, safe);
+    if dimensions {
+        check_dimensions(&path);
+        return;
+    }
+
+    run(&path, safe, rigorous);
 }
 
 fn help() {
@@ -121,5 +148,7 @@ fn help() {
     println!("       {} --project new : creates a new project.", NAME);
     println!("       {} --version : shows interpreter's version.", NAME);
     println!("       {} --safe : disables some features for website's IDE security", NAME);
+    println!("       {} --rigorous : rounds interval bounds outward so results are guaranteed enclosures", NAME);
+    println!("       {} --check <filename> : reports unit mismatches without running the file.", NAME);
     println!("       {} --help : shows this dialog.", NAME);
 }
\ No newline at end of file
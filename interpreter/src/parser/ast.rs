@@ -1,3 +1,5 @@
+use crate::core::units::Ratio;
+use crate::core::value::Value;
 use crate::parser::lexer::TokenSpan;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -15,6 +17,10 @@ pub enum Expr {
     Literal {
         value: TokenSpan,
     },
+
+    Constant {
+        value: Value, // an interval or quantity operand kept inside a symbolic expression
+    },
     
     Unary {
         operator: TokenSpan,
@@ -35,6 +41,11 @@ pub enum Expr {
         statements: Vec<Stmt> 
     },
 
+    Given {
+        bindings: Vec<(TokenSpan, Expr)>, // name = value, only visible inside the block
+        statements: Vec<Stmt>,
+    },
+
     Call {
         callee: Box<Expr>,
         paren: TokenSpan, 
@@ -72,7 +83,7 @@ pub enum UnitExpr {
     Name(TokenSpan),
     Mul(Box<UnitExpr>, Box<UnitExpr>),
     Div(Box<UnitExpr>, Box<UnitExpr>),
-    Pow(Box<UnitExpr>, i32),
+    Pow(Box<UnitExpr>, Ratio),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -87,6 +98,12 @@ pub enum Stmt {
         initializer: Expr,
     },
 
+    Unknown {
+        name: TokenSpan,
+        domain: Option<Expr>, // unknown x in [0..100]
+        unit: Option<UnitExpr>, // unknown x: m
+    },
+
     Assign {
         name: TokenSpan,
         value: Expr,
@@ -98,6 +115,12 @@ pub enum Stmt {
         is_quiet: bool,
     },
 
+    SymbolicFunction {
+        name: TokenSpan,
+        params: Vec<TokenSpan>,
+        body: Expr,
+    },
+
     Panic,
     
     Expression {
@@ -119,6 +142,7 @@ pub enum Stmt {
     Function {
         name: TokenSpan,
         params: Vec<Parameter>,
+        returns: Option<UnitExpr>, // fn power(f: N, v: m/s) -> W
         body: Vec<Stmt>,
         is_public: bool
     },
@@ -156,4 +180,5 @@ pub enum IfPolicy {
 pub struct Parameter {
     pub name: TokenSpan,
     pub default: Option<Expr>,
+    pub unit: Option<UnitExpr>, // checked on every call
 }
\ No newline at end of file
@@ -19,6 +19,7 @@ pub enum Token {
     Unknown,
     Symbolic,
     Quiet,
+    Given,
     Public,
     If,
     Elif,
@@ -31,6 +32,7 @@ pub enum Token {
     Match,
     Any,
     Comma,
+    Colon,
     Dot,
 
     Loop,
@@ -214,6 +216,7 @@ impl Lexer {
             '{' => Ok(Some(Token::LBrace)),
             '}' => Ok(Some(Token::RBrace)),
             ',' => Ok(Some(Token::Comma)),
+            ':' => Ok(Some(Token::Colon)),
             '%' => Ok(Some(Token::Modulo)),
             '^' => Ok(Some(Token::Caret)),
 
@@ -355,6 +358,7 @@ impl Lexer {
             "unknown" => Token::Unknown,
             "symbolic" => Token::Symbolic,
             "quiet" => Token::Quiet,
+            "given" => Token::Given,
             "pub" => Token::Public,
             "if" => Token::If,
             "elif" => Token::Elif,
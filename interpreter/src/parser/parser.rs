@@ -1,6 +1,7 @@
 use crate::parser::lexer::{Token, TokenSpan};
 use crate::parser::ast::{Expr, IfPolicy, Parameter, Stmt, UnitExpr};
 use crate::core::error::{Error, ErrorKind, ErrorReporter};
+use crate::core::units::Ratio;
 use std::cell::RefCell;
 use std::rc::Rc;
 
@@ -113,7 +114,7 @@ impl Parser {
                 let path = self.advance().clone();
 
                 if let Token::Identifier(name) = &path.token { // Special case for units lib
-                    if name == "units" {
+                    if crate::libs::units::LIBRARIES.contains(&name.as_str()) {
                         self.allow_unit_suffix = true;
                     }
                 }
@@ -164,16 +165,26 @@ impl Parser {
                 let param_name = self.consume_identifier("Expect parameter name")?;
                 let mut default = None;
 
+                let unit = match self.match_token(Token::Colon) {
+                    true => Some(self.unit_expr()?),
+                    false => None,
+                };
+
                 if self.match_token(Token::Assign) {
                     default = Some(self.expression()?);
                 }
 
-                parameters.push(Parameter { name: param_name, default });
+                parameters.push(Parameter { name: param_name, default, unit });
                 if !self.match_token(Token::Comma) { break; }
             }
         }
         
         self.consume(Token::RParen, "Expect ')' after parameters")?;
+
+        let returns = match self.match_token(Token::Arrow) {
+            true => Some(self.unit_expr()?),
+            false => None,
+        };
         
         self.skip_newlines();
         self.consume(Token::LBrace, "Expect '{' before function body")?;
@@ -183,6 +194,7 @@ impl Parser {
         Ok(Stmt::Function {
             name,
             params: parameters,
+            returns,
             body,
             is_public,
         })
@@ -214,26 +226,53 @@ impl Parser {
 
     fn symbolic_declaration(&mut self, is_quiet: bool) -> Result<Stmt, Error> {
         let name = self.consume_identifier("Expect variable name")?;
+        if self.match_token(Token::LParen) {
+            if is_quiet {
+                return Err(self.report_error(self.previous().clone(), "Quiet values can't take parameters, use 'fn' instead"));
+            }
+            return self.symbolic_function(name);
+        }
         self.consume(Token::Assign, "Expect '=' after name")?;
         let initializer = self.expression()?;
         self.end_stmt()?;
         Ok(Stmt::Symbolic { name, initializer, is_quiet })
     }
 
+    // symbolic f(x, y) = x^2 + y
+    #[allow(clippy::result_large_err)]
+    fn symbolic_function(&mut self, name: TokenSpan) -> Result<Stmt, Error> {
+        let mut params = Vec::new();
+        if !self.check(&Token::RParen) {
+            loop {
+                params.push(self.consume_identifier("Expect parameter name")?);
+                if !self.match_token(Token::Comma) { break; }
+            }
+        }
+
+        self.consume(Token::RParen, "Expect ')' after parameters")?;
+        self.consume(Token::Assign, "Expect '=' after parameters")?;
+        let body = self.expression()?;
+        self.end_stmt()?;
+        Ok(Stmt::SymbolicFunction { name, params, body })
+    }
+
     fn unknown_declaration(&mut self) -> Result<Stmt, Error> {
         let name = self.consume_identifier("Expect variable name after 'unknown'")?;
-        self.end_stmt()?;
-        
-        let unknown_span = TokenSpan { 
-            token: Token::Unknown, 
-            line: name.line, 
-            column: name.column 
+
+        let unit = if self.match_token(Token::Colon) {
+            Some(self.unit_expr()?)
+        } else {
+            None
         };
 
-        Ok(Stmt::Let { 
-            name, 
-            initializer: Expr::Literal { value: unknown_span } 
-        })
+        let domain = if self.match_token(Token::In) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        self.end_stmt()?;
+        Ok(Stmt::Unknown { name, domain, unit })
     }
 
     fn let_declaration(&mut self) -> Result<Stmt, Error> {
@@ -462,7 +501,12 @@ impl Parser {
             if self.match_token(Token::LParen) {
                 expr = self.finish_call(expr)?;
             } else if self.match_token(Token::Dot) {
-                let name = self.consume_identifier("Expect property name after '.'")?;
+                let name = if self.match_token(Token::In) {
+                    // a keyword, but also the unit conversion method, `d.in(km)`
+                    TokenSpan { token: Token::Identifier("in".to_string()), ..self.previous().clone() }
+                } else {
+                    self.consume_identifier("Expect property name after '.'")?
+                };
                 expr = Expr::Get { object: Box::new(expr), name };
             } else if self.match_token(Token::LBracket) {
                 let index = self.expression()?;
@@ -518,6 +562,10 @@ impl Parser {
             return Ok(Expr::Block { statements });
         }
 
+        if self.match_token(Token::Given) {
+            return self.given_expression();
+        }
+
         if self.match_token(Token::Number(0.0)) {
             let expr = Expr::Literal { value: self.previous().clone() };
             return self.maybe_unit_suffix(expr);
@@ -575,6 +623,64 @@ impl Parser {
         Err(self.report_error(self.peek().clone(), "Expect expression"))
     }
 
+    // given x = 5, y = [1..2] { ... }
+    #[allow(clippy::result_large_err)]
+    fn given_expression(&mut self) -> Result<Expr, Error> {
+        let mut bindings = Vec::new();
+        loop {
+            let name = self.consume_identifier("Expect variable name after 'given'")?;
+            self.consume(Token::Assign, "Expect '=' after variable name")?;
+            bindings.push((name, self.expression()?));
+
+            if !self.match_token(Token::Comma) {
+                break;
+            }
+        }
+
+        self.consume(Token::LBrace, "Expect '{' after given bindings")?;
+        let statements = self.block()?;
+        Ok(Expr::Given { bindings, statements })
+    }
+
+    /// A lone number or interval with an optional unit, `12.5 km/h` or `[3..4] kg`, the text
+    /// `units.parse` reads. Nothing else is accepted, so parsing data never runs code
+    #[allow(clippy::result_large_err)]
+    pub fn quantity(&mut self) -> Result<Expr, Error> {
+        let value = if self.match_token(Token::LBracket) {
+            let bracket = self.previous().clone();
+            let min = self.signed_number()?;
+            self.consume(Token::RangeSep, "Expect '..' in interval")?;
+            let max = self.signed_number()?;
+            self.consume(Token::RBracket, "Expect ']' after interval")?;
+            Expr::Interval { min: Box::new(min), max: Box::new(max), bracket }
+        } else {
+            self.signed_number()?
+        };
+
+        let expr = if self.peek_type(Token::Identifier("".to_string())) {
+            Expr::Quantity { value: Box::new(value), unit: self.unit_expr()? }
+        } else {
+            value
+        };
+
+        while self.match_token(Token::NewLine) {}
+        if !self.is_at_end() {
+            return Err(self.report_error(self.peek().clone(), "Unexpected text after the quantity"));
+        }
+        Ok(expr)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn signed_number(&mut self) -> Result<Expr, Error> {
+        if self.match_token(Token::Minus) {
+            let operator = self.previous().clone();
+            let right = self.signed_number()?;
+            return Ok(Expr::Unary { operator, right: Box::new(right) });
+        }
+        let number = self.consume_number("Expect a number")?;
+        Ok(Expr::Literal { value: number })
+    }
+
     fn maybe_unit_suffix(&mut self, expr: Expr) -> Result<Expr, Error> {
         if !self.allow_unit_suffix {
             return Ok(expr);
@@ -616,30 +722,43 @@ impl Parser {
         let mut expr = UnitExpr::Name(name);
 
         if self.match_token(Token::Caret) {
-            let exponent = self.consume_number("Expect integer exponent after '^'")?;
-            let exp_val = match exponent.token {
-                Token::Number(n) => n,
-                _ => {
-                    return Err(self.report_error(
-                        exponent,
-                        "Unit exponent must be a number",
-                    ))
-                }
+            let exponent = if self.match_token(Token::LParen) {
+                // m^(1/2)
+                let (num, token) = self.unit_exponent_number()?;
+                let den = match self.match_token(Token::Slash) {
+                    true => self.unit_exponent_number()?.0,
+                    false => 1.0,
+                };
+                self.consume(Token::RParen, "Expect ')' after unit exponent")?;
+                (num / den, token)
+            } else {
+                self.unit_exponent_number()?
             };
 
-            if exp_val.fract() != 0.0 {
+            let Some(ratio) = Ratio::from_f64(exponent.0) else {
                 return Err(self.report_error(
-                    exponent,
-                    "Unit exponent must be an integer",
+                    exponent.1,
+                    "Unit exponent must be a whole number or a simple fraction",
                 ));
-            }
+            };
 
-            expr = UnitExpr::Pow(Box::new(expr), exp_val as i32);
+            expr = UnitExpr::Pow(Box::new(expr), ratio);
         }
 
         Ok(expr)
     }
 
+    // A number with an optional minus, `m^-1`
+    #[allow(clippy::result_large_err)]
+    fn unit_exponent_number(&mut self) -> Result<(f64, TokenSpan), Error> {
+        let sign = if self.match_token(Token::Minus) { -1.0 } else { 1.0 };
+        let exponent = self.consume_number("Expect exponent after '^'")?;
+        match exponent.token {
+            Token::Number(n) => Ok((sign * n, exponent)),
+            _ => Err(self.report_error(exponent, "Unit exponent must be a number")),
+        }
+    }
+
     fn end_stmt(&mut self) -> Result<(), Error> {
         if self.is_at_end() { return Ok(()); }
         if self.match_token(Token::NewLine) { return Ok(()); }
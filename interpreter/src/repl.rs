@@ -75,8 +75,8 @@ impl Highlighter for RLHelper {
     }
 }
 
-pub fn run_repl(safe_mode: bool, name: &str, version: &str) {
-    let mut interpreter = SKInterpreter::new_with_options(safe_mode);
+pub fn run_repl(safe_mode: bool, rigorous: bool, name: &str, version: &str) {
+    let mut interpreter = SKInterpreter::new_with_options(safe_mode).rigorous(rigorous);
     let mut rl = rustyline::Editor::<RLHelper, rustyline::history::DefaultHistory>::new()
         .expect("Failed to create editor");
     
@@ -1,6 +1,8 @@
 use sk_lang::SKInterpreter;
 use sk_lang::core::units::Unit;
 use sk_lang::core::value::{SKBool, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 
 #[test]
 fn evals_basic_expression() {
@@ -247,4 +249,545 @@ fn library_import_alias() {
         .expect("execution should succeed");
 
     assert_eq!(result, Value::Number(5.0));
-}
\ No newline at end of file
+}
+
+#[test]
+fn evals_rigorous_intervals() {
+    let mut interpreter = SKInterpreter::new().rigorous(true);
+    let result1 = interpreter
+        .execute_string("[0.1..0.2] + [0.2..0.3]".to_string())
+        .expect("execution should succeed");
+    let result2 = interpreter
+        .execute_string("[1..2] * 3".to_string())
+        .expect("execution should succeed");
+    let result3 = interpreter
+        .execute_string("import math\nmath.PI".to_string())
+        .expect("execution should succeed");
+    let result4 = interpreter
+        .execute_string("[1..2] * 0.1".to_string())
+        .expect("execution should succeed");
+    let result5 = interpreter
+        .execute_string("import math\n[0.1 + 0.2, math.floor(2.5 * 1.1)]".to_string())
+        .expect("execution should succeed");
+
+    assert_eq!(result1, Value::Interval(0.29999999999999993, 0.5000000000000001));
+    assert_eq!(result2, Value::Interval(3.0, 6.0));
+    assert_eq!(result3, Value::Interval(std::f64::consts::PI, std::f64::consts::PI.next_up()));
+    assert_eq!(result4, Value::Interval(0.09999999999999999, 0.20000000000000004));
+    assert_eq!(result5, Value::Array(vec![Value::Number(0.1 + 0.2), Value::Number(2.0)]));
+}
+
+#[test]
+fn evals_refine() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("let x = [0..1]\nsymbolic z = x * (1 - x)\nrefine(z, 2)".to_string())
+        .expect("execution should succeed");
+
+    assert_eq!(result, Value::Array(vec![Value::Interval(0.0, 0.375), Value::Number(4.0)]));
+}
+
+#[test]
+fn evals_roots() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("import solve\nfn f(x) { x^2 - 2 }\nsolve.roots(f, [-3..3])".to_string())
+        .expect("execution should succeed");
+
+    let Value::Array(roots) = result else { panic!("roots() should return an array") };
+    assert_eq!(roots.len(), 2);
+    for (root, expected) in roots.iter().zip([-std::f64::consts::SQRT_2, std::f64::consts::SQRT_2]) {
+        let Value::Interval(min, max) = root else { panic!("roots should be intervals") };
+        assert!(*min <= expected && expected <= *max && max - min < 1e-9);
+    }
+
+    // The f64 values of PI and 2 * PI are just below the real ones, so the bounds can't be equal
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("import solve\nimport math\nlet t = [0..10]\nsymbolic wave = math.sin(t)\n[solve.roots(wave, [1..7]), 0.1 + [0.2..0.2]]".to_string())
+        .expect("execution should succeed");
+
+    let Value::Array(parts) = result else { panic!("execution should give an array") };
+    let [Value::Array(roots), sum] = parts.as_slice() else { panic!("roots() should return an array") };
+    assert_eq!(*sum, Value::Interval(0.1 + 0.2, 0.1 + 0.2), "rigorous mode should end with roots()");
+    assert_eq!(roots.len(), 2);
+    for (root, expected) in roots.iter().zip([std::f64::consts::PI, std::f64::consts::TAU]) {
+        let Value::Interval(min, max) = root else { panic!("roots should be intervals") };
+        assert!(*min <= expected && expected < *max);
+    }
+
+    // A user function named like a math one isn't differentiated as the math one
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("import solve\nfn sin(x) { x - 1 }\nlet t = [0..3]\nsymbolic m = sin(t)\nsolve.roots(m, [0..3])".to_string())
+        .expect("execution should succeed");
+
+    let Value::Array(roots) = result else { panic!("roots() should return an array") };
+    let [Value::Interval(min, max)] = roots.as_slice() else { panic!("roots() should find the root") };
+    assert!(*min <= 1.0 && 1.0 <= *max);
+}
+
+#[test]
+fn evals_minimize() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("import solve\nfn f(x) { (x - 1)^2 + 3 }\nsolve.minimize(f, [-3..3])".to_string())
+        .expect("execution should succeed");
+
+    let Value::Array(parts) = result else { panic!("minimize() should return an array") };
+    let [Value::Interval(low, high), Value::Interval(min, max)] = parts.as_slice() else {
+        panic!("minimize() should return the optimum and its location")
+    };
+    assert!(*low <= 3.0 && 3.0 <= *high && high - low <= 1e-6);
+    assert!(*min <= 1.0 && 1.0 <= *max);
+}
+
+#[test]
+fn evals_integrate() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("import solve\nfn f(x) { x^2 }\nsolve.integrate(f, [0..3])".to_string())
+        .expect("execution should succeed");
+
+    let Value::Interval(min, max) = result else { panic!("integrate() should return an interval") };
+    assert!(min <= 9.0 && 9.0 <= max && max - min <= 1e-6);
+}
+
+#[test]
+fn evals_sensitivity() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("let x = [0..4]\nlet y = [1..2]\nsymbolic z = x + y\nsensitivity(z)".to_string())
+        .expect("execution should succeed");
+
+    assert_eq!(
+        result,
+        Value::Array(vec![
+            Value::Array(vec![Value::String("x".into()), Value::Number(4.0)]),
+            Value::Array(vec![Value::String("y".into()), Value::Number(1.0)]),
+        ])
+    );
+}
+
+#[test]
+fn tracks_provenance() {
+    let mut interpreter = SKInterpreter::new();
+    interpreter
+        .execute_string("let temperature = [18..24]\nlet threshold = 20\nlet hot = temperature > threshold".to_string())
+        .expect("execution should succeed");
+
+    assert_eq!(
+        interpreter.why("hot"),
+        Some(vec!["partial because `temperature` [18..24] overlaps `threshold` 20 at line 3".to_string()])
+    );
+    assert_eq!(interpreter.why("threshold"), Some(vec!["20 is known exactly".to_string()]));
+}
+
+#[test]
+fn evals_graph() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("let x = [0..1]\nsymbolic z = x * (1 - x)\ngraph(z)".to_string())
+        .expect("execution should succeed");
+
+    let Value::String(dot) = result else { panic!("graph() should return a string") };
+    assert!(dot.starts_with("digraph {"));
+    assert_eq!(dot.matches("label=\"x\\n= [0..1]\"").count(), 1);
+    assert_eq!(dot.matches(" -> ").count(), 4);
+    assert!(dot.contains("label=\"*\\n= [0..1]\""));
+}
+
+#[test]
+fn evals_given_block() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string(
+            "let x = [0..10]\nlet y = 1\nsymbolic z = x * 2\nlet r = given x = 3 {\n    y = 100\n    resolve(z)\n}\n[r, x, y]"
+                .to_string(),
+        )
+        .expect("execution should succeed");
+
+    assert_eq!(
+        result,
+        Value::Array(vec![Value::Number(6.0), Value::Interval(0.0, 10.0), Value::Number(1.0)])
+    );
+
+    let mut interpreter = SKInterpreter::new();
+    let inside = interpreter
+        .execute_string("import units\ngiven n = 2 {\n    units.define(\"pace\", 0.75 m)\n    (n * 1 pace).in(m)\n}".to_string())
+        .expect("execution should succeed");
+    assert_eq!(inside, Value::Number(1.5));
+
+    let mut interpreter = SKInterpreter::new();
+    let leaked = "import units\ngiven n = 2 {\n    units.define(\"pace\", 0.75 m)\n}\n(2 pace).in(m)";
+    assert!(interpreter.execute_string(leaked.to_string()).is_err());
+}
+
+#[test]
+fn evals_scenarios() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("let x = [0..1]\nsymbolic z = x * 2\nscenarios(z, [[\"x\", [1, 3]]])".to_string())
+        .expect("execution should succeed");
+
+    assert_eq!(
+        result,
+        Value::Array(vec![
+            Value::Array(vec![Value::String("x".into()), Value::String("result".into())]),
+            Value::Array(vec![Value::Number(1.0), Value::Number(2.0)]),
+            Value::Array(vec![Value::Number(3.0), Value::Number(6.0)]),
+        ])
+    );
+
+    let unknown = interpreter.execute_string("scenarios(z, [[\"y\", [1, 3]]])".to_string());
+    assert!(unknown.is_err_and(|e| e.to_string().contains("'y'")));
+}
+
+#[test]
+fn evals_typed_unknown() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("import units\nunknown x in [0..100]\nunknown d: km\n[x * 2, d / 2 s, known(x)]".to_string())
+        .expect("execution should succeed");
+
+    assert_eq!(
+        result,
+        Value::Array(vec![
+            Value::Interval(0.0, 200.0),
+            Value::Quantity { value: Box::new(Value::Unknown), unit: Unit::base("m").div(&Unit::base("s")) },
+            Value::Bool(SKBool::False),
+        ])
+    );
+
+    let mut interpreter = SKInterpreter::new();
+    assert!(interpreter.execute_string("unknown x in [0..100]\nx = 150".to_string()).is_err());
+
+    let mut interpreter = SKInterpreter::new();
+    assert!(interpreter.execute_string("import units\nunknown d: m\nd = 5 s".to_string()).is_err());
+}
+
+#[test]
+fn resolves_partially() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("unknown a\nlet b = [1..3]\nsymbolic z = 2 + a * b\nstr(resolve(z))".to_string())
+        .expect("execution should succeed");
+
+    assert_eq!(result, Value::String("(2 + (a * [1..3]))".to_string()));
+}
+
+#[test]
+fn compares_symbolics() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string(
+            "let x = 3\nsymbolic A = x + 2 * x\nsymbolic B = (x * 2) + x\nunknown u\nsymbolic E = u + 1\nsymbolic G = u + 2\n[A == B, A == 12, A == 9, E == G]"
+                .to_string(),
+        )
+        .expect("execution should succeed");
+
+    assert_eq!(
+        result,
+        Value::Array(vec![
+            Value::Bool(SKBool::True),
+            Value::Bool(SKBool::False),
+            Value::Bool(SKBool::True),
+            Value::Bool(SKBool::Partial),
+        ])
+    );
+
+    let hash = |source: &str| {
+        let mut interpreter = SKInterpreter::new();
+        let Ok(Value::Symbolic { expression, .. }) = interpreter.execute_string(source.to_string()) else {
+            panic!("expected a symbolic value");
+        };
+        let mut hasher = DefaultHasher::new();
+        expression.hash(&mut hasher);
+        hasher.finish()
+    };
+    assert_eq!(hash("let a = 1\nsymbolic s = a * 2 + 1\ns"), hash("let a = 1\n\nsymbolic s = (1 + 2 * a)\ns"));
+
+    // `+` joins strings too, so its operands keep their order unless they are surely numbers
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("unknown b\nsymbolic p = \"x\" + b\nsymbolic q = b + \"x\"\np == q".to_string())
+        .expect("execution should succeed");
+    assert_eq!(result, Value::Bool(SKBool::Partial));
+}
+
+#[test]
+fn detects_symbolic_cycles() {
+    let mut interpreter = SKInterpreter::new();
+    assert!(interpreter.execute_string("symbolic x = x + 1".to_string()).is_err());
+
+    let mut interpreter = SKInterpreter::new();
+    assert!(interpreter.execute_string("quiet p = q + 1\nquiet q = p + 1".to_string()).is_err());
+
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("let a = 1\nquiet q = a * 10\nlet first = q\na = 2\n[first, q, q]".to_string())
+        .expect("execution should succeed");
+
+    assert_eq!(result, Value::Array(vec![Value::Number(10.0), Value::Number(20.0), Value::Number(20.0)]));
+}
+
+#[test]
+fn evals_symbolic_function() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string(
+            "let c = 4\nsymbolic f(x) = x^2 + c\nunknown u\nlet df = derivative(f)\n[f(3), f([1..2]), str(f(u)), df(3)]"
+                .to_string(),
+        )
+        .expect("execution should succeed");
+
+    assert_eq!(
+        result,
+        Value::Array(vec![
+            Value::Number(13.0),
+            Value::Interval(5.0, 8.0),
+            Value::String("((u ^ 2) + 4)".to_string()),
+            Value::Number(6.0),
+        ])
+    );
+}
+
+#[test]
+fn evals_unit_conversion() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("import units\nlet d = 5 km\nlet v = d / 2 h\n[d.in(m), to(v, km/h), str(d + 300 m)]".to_string())
+        .expect("execution should succeed");
+
+    assert_eq!(
+        result,
+        Value::Array(vec![Value::Number(5000.0), Value::Number(2.5), Value::String("5.3 km".to_string())])
+    );
+
+    let mut interpreter = SKInterpreter::new();
+    assert!(interpreter.execute_string("import units\nlet d = 5 km\nd.in(s)".to_string()).is_err());
+
+    // unit names are only values where a unit is expected
+    let mut interpreter = SKInterpreter::new();
+    let err = interpreter.execute_string("import units\nlet x = ns".to_string()).unwrap_err();
+    assert!(err.message.contains("undefined variable 'ns'"));
+}
+
+#[test]
+fn evals_temperatures() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("import units\nlet t = 20 degC\nlet d = 30 degC - t\n[t.in(degF), to(68 degF, degC), d.in(deltaF), str(t + 5 deltaC)]".to_string())
+        .expect("execution should succeed");
+
+    assert_eq!(
+        result,
+        Value::Array(vec![Value::Number(68.0), Value::Number(20.0), Value::Number(18.0), Value::String("25 degC".to_string())])
+    );
+
+    let mut interpreter = SKInterpreter::new();
+    assert!(interpreter.execute_string("import units\n20 degC + 10 degC".to_string()).is_err());
+    assert!(interpreter.execute_string("import units\n(30 degC - 20 degC).in(degC)".to_string()).is_err());
+    assert!(interpreter.execute_string("import units\n(5 deltaC).in(degF)".to_string()).is_err());
+    assert!(interpreter.execute_string("import units\n(20 degC).in(deltaC)".to_string()).is_err());
+
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("import units\n[(20 dC).in(K), (300 dK).in(degC), 20 dC == 20 degC]".to_string())
+        .expect("execution should succeed");
+    assert_eq!(result, Value::Array(vec![Value::Number(293.15), Value::Number(26.85), Value::Bool(SKBool::True)]));
+}
+
+#[test]
+fn evals_si_prefixes() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("import units\nunits.define(\"mph\", 0.44704 m/s)\n[(2 MPa).in(Pa), 5 µs .in(us), 3 kmph .in(mph), str(20 mA)]".to_string())
+        .expect("execution should succeed");
+
+    assert_eq!(
+        result,
+        Value::Array(vec![Value::Number(2e6), Value::Number(5.0), Value::Number(3000.0), Value::String("20 mA".to_string())])
+    );
+}
+
+#[test]
+fn evals_unit_packs() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("import units\nimport imperial\nimport nonsi\n[3 ft .in(inch), (30 psi).in(kPa), 2 week .in(day), 1 MiB .in(KiB), str(5 ft + 6 inch)]".to_string())
+        .expect("execution should succeed");
+
+    assert_eq!(
+        result,
+        Value::Array(vec![
+            Value::Number(36.0),
+            Value::Number(206.842718795),
+            Value::Number(14.0),
+            Value::Number(1024.0),
+            Value::String("5.5 ft".to_string()),
+        ])
+    );
+}
+
+#[test]
+fn evals_unit_math() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("import units\nimport math\nlet n = 3 V / (4 Hz)^0.5\n[str(math.sqrt(16 m^2)), str(n * n), str(math.hypot(3 m, 4 m)), str(math.min(3 km, 200 m)), str(math.round(2.4 km))]".to_string())
+        .expect("execution should succeed");
+
+    assert_eq!(
+        result,
+        Value::Array(vec![
+            Value::String("4 m".to_string()),
+            Value::String("2.25 kg^2*m^4/A^2*s^5".to_string()),
+            Value::String("5 m".to_string()),
+            Value::String("200 m".to_string()),
+            Value::String("2 km".to_string()),
+        ])
+    );
+
+    let mut interpreter = SKInterpreter::new();
+    assert!(interpreter.execute_string("import units\nimport math\nmath.sin(3 m)".to_string()).is_err());
+}
+
+#[test]
+fn evals_unit_parsing() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("import units\nlet v = units.parse(\"12.5 km/h\")\n[str(v), str(units.parse(\"[3..4] kg\")), units.format(v, \"m/s\", 2), units.format(20 degC, degF)]".to_string())
+        .expect("execution should succeed");
+
+    assert_eq!(
+        result,
+        Value::Array(vec![
+            Value::String("12.5 km/h".to_string()),
+            Value::String("[3..4] kg".to_string()),
+            Value::String("3.47 m/s".to_string()),
+            Value::String("68 degF".to_string()),
+        ])
+    );
+
+    let mut interpreter = SKInterpreter::new();
+    let err = interpreter.execute_string("import units\nunits.parse(\"12 kmh\")".to_string()).unwrap_err();
+    assert!(err.message.contains("did you mean km"));
+    assert!(interpreter.execute_string("import units\nunits.format(3 m, km, 1e300)".to_string()).is_err());
+}
+
+#[test]
+fn evals_symbolic_units() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("import units\nunknown t: s\nunknown y\nsymbolic v = (10 m) / t\nlet w = v + 3 m/s\nlet f = 2 m + y\nt = 2 s\n[str(resolve(w)), str(f)]".to_string())
+        .expect("execution should succeed");
+
+    assert_eq!(
+        result,
+        Value::Array(vec![Value::String("8 m/s".to_string()), Value::String("unknown m".to_string())])
+    );
+
+    let mut interpreter = SKInterpreter::new();
+    let err = interpreter.execute_string("import units\nunknown q: kg\nsymbolic bad = q + 1 m".to_string()).unwrap_err();
+    assert!(err.message.contains("cannot add kg and m"));
+}
+
+#[test]
+fn checks_dimensions() {
+    let mut interpreter = SKInterpreter::new();
+    let errors = interpreter
+        .check_dimensions_string("import units\nlet d = 5 m\nlet t = 3 s\nfn speed(a, b) { a / b }\nlet v = speed(d, t) + 1 m/s\nlet bad = d + t\nif v > d { print(v) }".to_string())
+        .expect("check should parse");
+
+    let messages: Vec<(usize, String)> = errors.into_iter().map(|e| (e.token.line, e.message)).collect();
+    assert_eq!(
+        messages,
+        vec![
+            (6, "Unit mismatch: cannot add m and s".to_string()),
+            (7, "Unit mismatch: cannot compare m/s and m".to_string()),
+        ]
+    );
+
+    // `x` may still hold the number read from input, `y` is a length whatever the branch does
+    let mut interpreter = SKInterpreter::new();
+    let errors = interpreter
+        .check_dimensions_string("import units\nlet c = true\nlet x = num(input())\nlet y = 5 m\nif c { x = 5 m\ny = 2 m }\nx + 1\ny + 1 s".to_string())
+        .expect("check should parse");
+
+    let messages: Vec<(usize, String)> = errors.into_iter().map(|e| (e.token.line, e.message)).collect();
+    assert_eq!(messages, vec![(8, "Unit mismatch: cannot add m and s".to_string())]);
+}
+
+#[test]
+fn evals_unit_annotations() {
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("import units\nfn power(f: N, v: m/s) -> W { f * v }\nfn speed(v: km/h) { v }\n[str(power(10 N, 36 km/h)), str(speed(10 m/s))]".to_string())
+        .expect("execution should succeed");
+
+    assert_eq!(
+        result,
+        Value::Array(vec![Value::String("100 W".to_string()), Value::String("36 km/h".to_string())])
+    );
+
+    let mut interpreter = SKInterpreter::new();
+    let err = interpreter.execute_string("import units\nfn power(f: N, v: m/s) -> W { f * v }\npower(10 N, 3 s)".to_string()).unwrap_err();
+    assert!(err.message.contains("Argument 'v' should be in m/s, got 3 s"));
+
+    let mut interpreter = SKInterpreter::new();
+    let err = interpreter.execute_string("import units\nfn wrong(f: N) -> W { f }\nwrong(2 N)".to_string()).unwrap_err();
+    assert!(err.message.contains("The result should be in W"));
+}
+
+#[test]
+fn evals_unit_systems() {
+    // Named after the process so parallel test runs don't share the scripts
+    let dir = std::env::temp_dir();
+    let shop = dir.join(format!("sk_shop_units_{}.sk", std::process::id()));
+    let money = dir.join(format!("sk_money_units_{}.sk", std::process::id()));
+    std::fs::write(&shop, "import units\nunits.dimension(\"item\")\nunits.define(\"dozen\", 12 item)").unwrap();
+    std::fs::write(&money, "import units\nunits.dimension(\"USD\")\nunits.dimension(\"item\")\nunits.define(\"dozen\", 10 USD)").unwrap();
+
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string(format!(
+            "import units\nimport \"{}\" as shop\nunits.use(shop)\nunits.dimension(\"USD\")\nlet price = 2.5 USD/item\n[str((3 dozen) * price), (4 dozen).in(item), str(units.parse(\"2 kUSD\"))]",
+            shop.display()
+        ))
+        .map_err(|e| e.message);
+
+    let mut interpreter = SKInterpreter::new();
+    let clash = interpreter
+        .execute_string(format!(
+            "import units\nimport \"{}\" as shop\nimport \"{}\" as money\nunits.use(shop)\nunits.use(money)",
+            shop.display(),
+            money.display()
+        ))
+        .map_err(|e| e.message);
+
+    std::fs::remove_file(&shop).unwrap();
+    std::fs::remove_file(&money).unwrap();
+
+    assert_eq!(
+        result,
+        Ok(Value::Array(vec![
+            Value::String("90 USD".to_string()),
+            Value::Number(48.0),
+            Value::String("2 kUSD".to_string()),
+        ]))
+    );
+    assert!(clash.unwrap_err().contains("'dozen' is already 12 item"));
+
+    let mut interpreter = SKInterpreter::new();
+    let result = interpreter
+        .execute_string("import units as u\nu.define(\"furl\", 201 m)\n[(2 furl).in(m), u.format(2010 m, furl), str(u.parse(\"3 kfurl\"))]".to_string())
+        .expect("execution should succeed");
+    assert_eq!(
+        result,
+        Value::Array(vec![Value::Number(402.0), Value::String("10 furl".to_string()), Value::String("3 kfurl".to_string())])
+    );
+
+    let mut interpreter = SKInterpreter::new();
+    let err = interpreter.execute_string("import units\nunits.dimension(\"kg\")".to_string()).unwrap_err();
+    assert!(err.message.contains("'kg' is already a unit"));
+}